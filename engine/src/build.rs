@@ -1,29 +1,94 @@
+use crate::deadline::Deadline;
+use crate::labels::Labels;
+use crate::models::output::Phase;
 use crate::models::*;
 use crate::path_bag::PathBag;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::sync::Arc;
 
-/// Builds a set of interesting paths
-pub fn build(world: &World) -> PathBag {
-    let mut base_paths = PathBag::new(world.max_bag_items);
-    let mut finished_paths = PathBag::new(world.max_bag_items);
+/// The state of the search after an iteration of one of its phases
+#[derive(Debug)]
+pub struct Progress<'a> {
+    pub phase: Phase,
+    /// Starts at 1 in each phase
+    pub iteration: usize,
+    /// How many paths are left for the next iteration
+    pub pending: usize,
+    /// The paths to extend in the next iteration, when they are kept in a bag
+    pub base_paths: Option<&'a PathBag>,
+    pub finished_paths: &'a PathBag,
+    /// How many extended paths were discarded by state dominance
+    pub pruned: usize,
+}
 
-    // Add seed paths, based on each desired starting position
-    for site in &world.sites {
-        if site.can_start_here {
-            let path = Path::empty(site.id, world.min_start_at);
-            base_paths.add(path);
-        }
+/// The finished paths of a search
+#[derive(Debug)]
+pub struct Built {
+    pub paths: PathBag,
+    /// The sites visited by at least one of the extended paths
+    pub reached_sites: SiteSet,
+}
+
+/// Builds a set of interesting paths, stopping early if the deadline is reached. The progress is
+/// reported after each iteration
+pub fn build(world: &World, deadline: &Deadline, on_progress: &mut impl FnMut(Progress)) -> Built {
+    let mut base_paths = PathBag::new(world.max_bag_items, world.objectives.clone());
+    let mut finished_paths = PathBag::new(world.max_bag_items, world.objectives.clone());
+    let mut reached_sites = SiteSet::new();
+
+    for path in seed_paths(world) {
+        base_paths.add(path);
     }
 
-    while !base_paths.is_empty() {
+    let mut iteration = 0;
+    while !base_paths.is_empty() && !deadline.is_reached() {
         log::info!(
             "Build iteration starting from {} paths, by score = {:?}",
             base_paths.len(),
             base_paths.count_by_score()
         );
-        base_paths = build_iteration(world, &mut finished_paths, base_paths);
+        let pruned;
+        (base_paths, pruned) = build_iteration(
+            world,
+            deadline,
+            &mut finished_paths,
+            &mut reached_sites,
+            base_paths,
+        );
+        iteration += 1;
+        on_progress(Progress {
+            phase: Phase::Build,
+            iteration,
+            pending: base_paths.len(),
+            base_paths: Some(&base_paths),
+            finished_paths: &finished_paths,
+            pruned,
+        });
+    }
+
+    // The deadline was reached: keep the complete paths that were not closed yet
+    for base_path in base_paths.into_paths() {
+        for closed_path in close_if_complete(world, &base_path) {
+            finished_paths.add(closed_path);
+        }
     }
 
-    finished_paths
+    Built {
+        paths: finished_paths,
+        reached_sites,
+    }
+}
+
+/// The paths to start from, one for each departure site of each traveler
+pub fn seed_paths(world: &World) -> Vec<Path> {
+    let mut paths = vec![];
+    for traveler in &world.travelers {
+        for &site in traveler.departure_sites() {
+            paths.push(Path::empty(traveler, site));
+        }
+    }
+    paths
 }
 
 #[derive(Debug)]
@@ -33,46 +98,142 @@ struct ExtensionInfo {
     earliest_service_start: Timestamp,
 }
 
-fn build_iteration(world: &World, finished_paths: &mut PathBag, base_paths: PathBag) -> PathBag {
-    let mut new_bag = PathBag::new(world.max_bag_items);
+fn build_iteration(
+    world: &World,
+    deadline: &Deadline,
+    finished_paths: &mut PathBag,
+    reached_sites: &mut SiteSet,
+    base_paths: PathBag,
+) -> (PathBag, usize) {
+    let mut new_bag = PathBag::new(world.max_bag_items, world.objectives.clone());
 
-    // For each base path, try all possible extensions
-    for base_path in base_paths.into_paths() {
-        extend_path(world, &base_path, &mut new_bag);
+    // Only keep extended paths that are not dominated by another one in the same state
+    let mut labels = Labels::new(world);
 
-        if base_path.visited_sites.is_superset(&world.must_visit) {
-            finished_paths.add(base_path);
+    let base_paths = base_paths.into_paths().collect_vec();
+    let results = extend_paths(world, deadline, &base_paths, world.max_tested_extensions);
+    for (extended_paths, closed_paths) in results {
+        for path in extended_paths {
+            reached_sites.union_with(&path.visited_sites);
+            labels.add(path);
         }
+        for closed_path in closed_paths {
+            finished_paths.add(closed_path);
+        }
+    }
+
+    log::info!(
+        "Pruned {} out of {} extended paths by state dominance",
+        labels.pruned,
+        labels.pruned + labels.len()
+    );
+    let pruned = labels.pruned;
+    for path in labels.into_paths() {
+        new_bag.add(path);
     }
 
-    new_bag
+    (new_bag, pruned)
 }
 
-/// Try at most `max_candidates` modifications to the given base path, adding one more stop at the
-/// end. Returns `true` if at least one new path was inserted into the resulting bag.
-fn extend_path(world: &World, base_path: &Path, sink: &mut PathBag) {
+/// Extend each base path and close the complete ones, returning the extended and the closed paths
+/// of each base path. This runs in parallel, but the results are in the order of the base paths,
+/// so merging them does not depend on the thread scheduling. Once the deadline is reached, the
+/// remaining base paths are only closed
+pub fn extend_paths(
+    world: &World,
+    deadline: &Deadline,
+    base_paths: &[Path],
+    max_tested_extensions: usize,
+) -> Vec<(Vec<Path>, Vec<Path>)> {
+    base_paths
+        .par_iter()
+        .map(|base_path| {
+            let mut extended_paths = vec![];
+            if !deadline.is_reached() {
+                extend_path(world, base_path, max_tested_extensions, &mut |path| {
+                    extended_paths.push(path)
+                });
+            }
+
+            (extended_paths, close_if_complete(world, base_path))
+        })
+        .collect()
+}
+
+/// Close the path if it visits all the sites that must be visited. A route handed over without
+/// any visit is not closed, since the previous routes are also closed on their own
+pub fn close_if_complete(world: &World, path: &Path) -> Vec<Path> {
+    let is_idle_hand_over = path.previous.is_some() && path.stops.is_empty();
+    if !is_idle_hand_over && path.visited_sites.is_superset(&world.must_visit) {
+        close_path(world, path)
+    } else {
+        vec![]
+    }
+}
+
+/// Try at most `max_tested_extensions` modifications to the given base path, adding one more stop
+/// at the end. Also try handing the path over to each one of the next travelers.
+pub fn extend_path(
+    world: &World,
+    base_path: &Path,
+    max_tested_extensions: usize,
+    sink: &mut impl FnMut(Path),
+) {
     let (end_in, end_at) = base_path.end();
+    let traveler = &world[base_path.traveler];
+
+    // Hand over to the next travelers. Empty routes are not handed over, since the same path is
+    // also seeded from the next traveler directly
+    if !base_path.stops.is_empty() {
+        for closed_path in close_path(world, base_path) {
+            let previous = Arc::new(closed_path);
+            for next_traveler in &world.travelers[traveler.id.as_usize() + 1..] {
+                for &site in next_traveler.departure_sites() {
+                    sink(Path::empty(next_traveler, site).after(Some(previous.clone())));
+                }
+            }
+        }
+    }
 
     // Collect all possible extensions
     let mut extensions = vec![];
     for site in &world.sites {
-        // Don't revisit
-        if site.visit == Visit::Never || base_path.visited_sites.contains(&site.id) {
+        // Don't revisit, even the sites completed before re-planning
+        if site.visit == Visit::Never
+            || base_path.visited_sites.contains(site.id)
+            || world.completed_sites.contains(site.id)
+        {
+            continue;
+        }
+
+        // Required previous visits must have been done
+        if !world
+            .precedences_to(site.id)
+            .all(|precedence| precedence.is_ordered(&world.completed_sites, &base_path.stops))
+        {
             continue;
         }
 
         // Ride must exist
-        let ride = match world.ride(end_in, site.id) {
+        let ride = match world.ride(end_in, site.id, end_at) {
             None => continue,
             Some(ride) => ride,
         };
 
         let earliest_arrival = end_at + ride;
-        extensions.push(ExtensionInfo {
-            site: site.id,
-            duty: None,
-            earliest_service_start: earliest_arrival,
-        });
+        if site.opening_hours.is_empty() {
+            extensions.push(ExtensionInfo {
+                site: site.id,
+                duty: None,
+                earliest_service_start: earliest_arrival,
+            });
+        } else if let Some(opening) = site.opening_after(earliest_arrival) {
+            extensions.push(ExtensionInfo {
+                site: site.id,
+                duty: None,
+                earliest_service_start: opening.start().max(earliest_arrival),
+            });
+        }
 
         for &duty in &site.duties {
             if earliest_arrival <= duty.start() {
@@ -88,16 +249,32 @@ fn extend_path(world: &World, base_path: &Path, sink: &mut PathBag) {
     // Collect the best candidate sites to extend this path
     if extensions.is_empty() {
         return;
-    } else if extensions.len() <= world.max_tested_extensions {
+    } else if extensions.len() <= max_tested_extensions {
         log::debug!("Selected {} valid extensions", extensions.len());
     } else {
         log::debug!(
             "Selected {} out of {} valid extensions",
-            world.max_tested_extensions,
+            max_tested_extensions,
             extensions.len(),
         );
-        extensions.sort_by_key(|info| info.earliest_service_start);
-        extensions.truncate(world.max_tested_extensions);
+        if world.has_prizes {
+            // Prefer the extensions that collect more prize for the time they take
+            let prize_rate = |info: &ExtensionInfo| {
+                let site = &world[info.site];
+                let time_spent = info.earliest_service_start - end_at + site.service_time;
+                (site.prize as i64, time_spent.as_s().max(1) as i64)
+            };
+            extensions.sort_by(|a, b| {
+                let (prize_a, time_a) = prize_rate(a);
+                let (prize_b, time_b) = prize_rate(b);
+                (prize_b * time_a)
+                    .cmp(&(prize_a * time_b))
+                    .then(a.earliest_service_start.cmp(&b.earliest_service_start))
+            });
+        } else {
+            extensions.sort_by_key(|info| info.earliest_service_start);
+        }
+        extensions.truncate(max_tested_extensions);
     }
 
     // Try each extension: the vec `new_stops` will be pushed and popped at every iteration
@@ -110,19 +287,306 @@ fn extend_path(world: &World, base_path: &Path, sink: &mut PathBag) {
         new_stops.push(StopSketch {
             site: extension.site,
             duty: extension.duty,
+            kind: StopKind::Visit,
         });
-        let new_path = Path::try_schedule(world, base_path.start_in, &new_stops);
+        let new_path = Path::try_schedule(world, traveler.id, base_path.start_in, &new_stops);
         new_stops.pop();
 
         if let Some(new_path) = new_path {
-            if let Some(max_end_at) = world.max_end_at {
-                if new_path.end().1 > max_end_at {
-                    log::debug!("Ignore path {} that ends too late", new_path);
-                    continue;
-                }
+            if ends_in_time(world, &new_path) {
+                sink(new_path.after(base_path.previous.clone()));
+            }
+        }
+    }
+}
+
+/// Return the variants of the given path whose last route ends where its traveler is allowed to.
+/// Routes that need to ride to their end are extended with a return stop.
+pub fn close_path(world: &World, path: &Path) -> Vec<Path> {
+    let traveler = &world[path.traveler];
+    let (end_in, _) = path.end();
+    let end_sites = match traveler.end {
+        PathEnd::Anywhere => return vec![path.clone()],
+        PathEnd::EndSite => &traveler.end_sites[..],
+        PathEnd::Start => &[traveler.return_site(path.start_in)],
+    };
+    if end_sites.contains(&end_in) {
+        return vec![path.clone()];
+    }
+
+    let mut stops = path.stops.iter().map(Stop::sketch).collect_vec();
+    let mut closed_paths = vec![];
+    for &end_site in end_sites {
+        stops.push(StopSketch {
+            site: end_site,
+            duty: None,
+            kind: StopKind::Return,
+        });
+        let closed_path = Path::try_schedule(world, traveler.id, path.start_in, &stops);
+        stops.pop();
+
+        if let Some(closed_path) = closed_path {
+            if ends_in_time(world, &closed_path) {
+                closed_paths.push(closed_path.after(path.previous.clone()));
+            }
+        }
+    }
+    closed_paths
+}
+
+pub fn ends_in_time(world: &World, path: &Path) -> bool {
+    match world[path.traveler].max_end_at {
+        Some(max_end_at) if path.end().1 > max_end_at => {
+            log::debug!("Ignore path {} that ends too late", path);
+            false
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_across_threads() {
+        let mut world = World::mock((0..8).map(|_| Site::mock()).collect());
+        world.travelers[0].min_start_at = Timestamp::from(0);
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.max_tested_extensions = 4;
+        world.max_bag_items = 5;
+        for from in 0..8 {
+            for to in 0..8 {
+                let ride = (from as i32 * 7 + to as i32 * 13) % 10 + 1;
+                world.ride_matrix.set(
+                    SiteId::from_usize(from),
+                    SiteId::from_usize(to),
+                    Duration::from(ride),
+                );
             }
+        }
+
+        let build_with_threads = |threads| {
+            let thread_pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            thread_pool
+                .install(|| build(&world, &Deadline::never(), &mut |_| {}).paths)
+                .into_paths()
+                .map(|path| path.to_string())
+                .collect_vec()
+        };
+        let sequential = build_with_threads(1);
+        assert!(!sequential.is_empty());
+        for _ in 0..5 {
+            assert_eq!(build_with_threads(4), sequential);
+        }
+    }
+
+    #[test]
+    fn close_each_end() {
+        let mut world = World::mock((0..3).map(|_| Site::mock()).collect());
+        for (from, to) in [(0, 1), (1, 2)] {
+            world.ride_matrix.set(
+                SiteId::from_usize(from),
+                SiteId::from_usize(to),
+                Duration::from(5),
+            );
+        }
+        let visit = [StopSketch {
+            site: SiteId::from(1),
+            duty: None,
+            kind: StopKind::Visit,
+        }];
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &visit).unwrap();
+        let close = |world: &World| {
+            close_path(world, &path)
+                .into_iter()
+                .map(|path| {
+                    let last = path.stops.last().unwrap();
+                    (last.site, last.kind, path.cost.total_ride)
+                })
+                .collect_vec()
+        };
+
+        // Anywhere
+        let visited = (SiteId::from(1), StopKind::Visit, Duration::from(5));
+        assert_eq!(close(&world), vec![visited]);
+
+        // At an end site, staying if already there
+        world.travelers[0].end = PathEnd::EndSite;
+        world.travelers[0].end_sites = vec![SiteId::from(1)];
+        assert_eq!(close(&world), vec![visited]);
+        world.travelers[0].end_sites = vec![SiteId::from(0), SiteId::from(2)];
+        assert_eq!(
+            close(&world),
+            vec![(SiteId::from(2), StopKind::Return, Duration::from(10))]
+        );
+
+        // Back to the start, when there is a ride back
+        world.travelers[0].end = PathEnd::Start;
+        assert_eq!(close(&world), vec![]);
+        world
+            .ride_matrix
+            .set(SiteId::from(1), SiteId::from(0), Duration::from(7));
+        assert_eq!(
+            close(&world),
+            vec![(SiteId::from(0), StopKind::Return, Duration::from(12))]
+        );
+    }
+
+    #[test]
+    fn prefer_prize_rate() {
+        let mut world = World::mock((0..4).map(|_| Site::mock()).collect());
+        world.sites[0].visit = Visit::Never;
+        for (site, prize, ride) in [(1, 10, 5), (2, 3, 1), (3, 0, 1)] {
+            world.sites[site].prize = prize;
+            world.ride_matrix.set(
+                SiteId::from(0),
+                SiteId::from_usize(site),
+                Duration::from(ride),
+            );
+        }
+        let path = Path::empty(&world.travelers[0], SiteId::from(0));
+        let extend = |world: &World, max_tested_extensions| {
+            let mut sites = vec![];
+            extend_path(world, &path, max_tested_extensions, &mut |path| {
+                sites.push(path.stops[0].site.as_usize())
+            });
+            sites
+        };
+
+        // Closer sites first
+        assert_eq!(extend(&world, 2), vec![2, 3]);
+
+        // More prize per time spent first: 3 per second, then 2 per second
+        world.has_prizes = true;
+        assert_eq!(extend(&world, 1), vec![2]);
+        assert_eq!(extend(&world, 2), vec![2, 1]);
+    }
+
+    #[test]
+    fn no_duplicate_plans() {
+        let mut world = World::mock((0..4).map(|_| Site::mock()).collect());
+        world.sites[0].visit = Visit::Never;
+        world.sites[3].visit = Visit::Never;
+        world.must_visit = [1, 2].into_iter().map(SiteId::from).collect();
+        world.max_tested_extensions = 10;
+        world.max_bag_items = 100;
+        let mut second = Traveler::mock();
+        second.id = TravelerId::from(1);
+        world.travelers.push(second);
+        for traveler in &mut world.travelers {
+            traveler.start_sites = vec![SiteId::from(0)];
+            traveler.end = PathEnd::EndSite;
+            traveler.end_sites = vec![SiteId::from(3)];
+        }
+        for from in 0..4 {
+            for to in 0..4 {
+                world.ride_matrix.set(
+                    SiteId::from_usize(from),
+                    SiteId::from_usize(to),
+                    Duration::from(1),
+                );
+            }
+        }
+
+        // The visits of each traveler, ignoring the routes without any
+        let plans = build(&world, &Deadline::never(), &mut |_| {})
+            .paths
+            .into_paths()
+            .map(|path| {
+                path.routes()
+                    .filter(|route| route.stops.iter().any(|stop| stop.kind == StopKind::Visit))
+                    .map(|route| {
+                        let visits = route
+                            .stops
+                            .iter()
+                            .filter(|stop| stop.kind == StopKind::Visit)
+                            .map(|stop| stop.site.as_usize())
+                            .collect_vec();
+                        (route.traveler.as_usize(), visits)
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+        assert_eq!(plans.len(), plans.iter().unique().count());
+        assert!(plans.contains(&vec![(1, vec![1, 2])]));
+        assert!(plans.contains(&vec![(1, vec![2]), (0, vec![1])]));
+    }
+
+    #[test]
+    fn stop_at_deadline() {
+        let mut world = World::mock(vec![Site::mock(), Site::mock()]);
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.max_tested_extensions = 1;
+        world.max_bag_items = 1;
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(1));
+
+        let deadline = Deadline::never();
+        assert!(!build(&world, &deadline, &mut |_| {}).paths.is_empty());
+        assert!(!deadline.was_reached());
+
+        // The complete paths are still closed
+        let deadline = Deadline::new(Some(std::time::Duration::ZERO));
+        assert!(!build(&world, &deadline, &mut |_| {}).paths.is_empty());
+        assert!(deadline.was_reached());
+
+        world.must_visit = [SiteId::from(1)].into_iter().collect();
+        let visit = [StopSketch {
+            site: SiteId::from(1),
+            duty: None,
+            kind: StopKind::Visit,
+        }];
+        let complete =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &visit).unwrap();
+        let incomplete = Path::empty(&world.travelers[0], SiteId::from(0));
+        let results = extend_paths(&world, &deadline, &[complete, incomplete], 1);
+        assert!(results[0].0.is_empty());
+        assert_eq!(results[0].1.len(), 1);
+        assert!(results[1].0.is_empty());
+        assert!(results[1].1.is_empty());
+    }
+
+    #[test]
+    fn replan_from_position() {
+        let mut world = World::mock((0..4).map(|_| Site::mock()).collect());
+        world.must_visit = (2..4).map(SiteId::from_usize).collect();
+        world.sites[0].visit = Visit::Never;
+        world.completed_sites = [SiteId::from(1)].into_iter().collect();
+        world.travelers[0].end = PathEnd::Start;
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.travelers[0].current = Some(CurrentPosition {
+            site: SiteId::from(1),
+            at: Timestamp::from(50),
+            started_in: SiteId::from(0),
+            work_since_break: Duration::ZERO,
+            total_work: Duration::ZERO,
+        });
+        world.max_tested_extensions = 4;
+        world.max_bag_items = 10;
+        for from in 0..4 {
+            for to in 0..4 {
+                world.ride_matrix.set(
+                    SiteId::from_usize(from),
+                    SiteId::from_usize(to),
+                    Duration::from(5),
+                );
+            }
+        }
 
-            sink.add(new_path);
+        let paths = build(&world, &Deadline::never(), &mut |_| {}).paths;
+        assert!(!paths.is_empty());
+        for path in paths.into_paths() {
+            assert_eq!(path.start_in, SiteId::from(1));
+            assert!(path.start_at >= Timestamp::from(50));
+            assert_eq!(path.stops.len(), 3);
+            assert!(path.stops.iter().all(|stop| stop.site != SiteId::from(1)));
+            assert_eq!(path.stops[2].site, SiteId::from(0));
         }
     }
 }
@@ -0,0 +1,179 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use traveling_simon::input;
+use traveling_simon::input::{Duration, Solver};
+
+/// Plan the routes of travelers visiting sites. Without a subcommand, solves the world read from
+/// stdin
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Find the best paths for a world
+    Solve {
+        #[command(flatten)]
+        io: InputOutput,
+        #[command(flatten)]
+        overrides: Overrides,
+        /// Write progress events as newline-delimited JSON, followed by the answer. The format is
+        /// ignored
+        #[arg(long)]
+        stream: bool,
+    },
+    /// Check that a world is valid, without solving it
+    Validate {
+        #[command(flatten)]
+        io: InputOutput,
+    },
+    /// Schedule a given itinerary in a world
+    Evaluate {
+        #[command(flatten)]
+        io: InputOutput,
+        /// The itinerary, as JSON
+        #[arg(long)]
+        itinerary: PathBuf,
+    },
+    /// Solve several worlds, one after the other
+    Batch {
+        /// The worlds, as JSON
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Write each answer to a file with the same name in this directory, so the input names
+        /// must be distinct. Otherwise, print one line per world
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        #[command(flatten)]
+        overrides: Overrides,
+    },
+    /// Solve worlds sent over HTTP, with the same contract as the web server
+    Serve {
+        #[command(flatten)]
+        options: ServeOptions,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ServeOptions {
+    #[arg(long, default_value = "127.0.0.1:3001")]
+    pub address: String,
+    /// How many requests are solved at the same time
+    #[arg(long, default_value_t = 2)]
+    pub workers: usize,
+    /// The largest accepted request body, in bytes
+    #[arg(long, default_value_t = 10_000_000)]
+    pub max_request_size: usize,
+    /// The longest time spent solving each request, after which the best paths so far are
+    /// returned
+    #[arg(long, default_value = "1m")]
+    pub timeout: Duration,
+    /// How many threads solve each request, whatever the request asks for
+    #[arg(long, default_value_t = 1)]
+    pub threads: i32,
+    /// The highest `maxTestedExtensions` accepted, greater values are lowered to it
+    #[arg(long, default_value_t = 100)]
+    pub max_tested_extensions: i32,
+    /// The highest `maxBagItems` accepted, greater values are lowered to it
+    #[arg(long, default_value_t = 10_000)]
+    pub max_bag_items: i32,
+    /// The highest `maxResults` accepted, greater values are lowered to it
+    #[arg(long, default_value_t = 100)]
+    pub max_results: i32,
+    /// The highest `maxImprovementSteps` accepted, greater values are lowered to it
+    #[arg(long, default_value_t = 1000)]
+    pub max_improvement_steps: i32,
+    /// Accept requests for the exact solver, whose memory use grows exponentially with the sites
+    #[arg(long)]
+    pub allow_exact: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct InputOutput {
+    /// Read the world from this file instead of stdin
+    #[arg(short, long)]
+    pub input: Option<PathBuf>,
+    /// Write to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    /// A single line of JSON
+    #[default]
+    Json,
+    /// Indented JSON
+    Pretty,
+}
+
+/// Replace the parameters given in the world
+#[derive(Debug, Default, Args)]
+pub struct Overrides {
+    #[arg(long)]
+    pub max_tested_extensions: Option<i32>,
+    #[arg(long)]
+    pub max_bag_items: Option<i32>,
+    #[arg(long)]
+    pub max_results: Option<i32>,
+    #[arg(long)]
+    pub max_improvement_steps: Option<i32>,
+    /// Like "30s" or "2m"
+    #[arg(long)]
+    pub time_limit: Option<Duration>,
+    #[arg(long)]
+    pub threads: Option<i32>,
+    #[arg(long, value_enum)]
+    pub solver: Option<SolverArg>,
+}
+
+/// The same as `Solver`, which the library does not tie to the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SolverArg {
+    /// Beam-like search, bounded by `max_tested_extensions` and `max_bag_items`
+    Heuristic,
+    /// Return the true Pareto front. Only practical for small worlds
+    Exact,
+}
+
+impl From<SolverArg> for Solver {
+    fn from(solver: SolverArg) -> Self {
+        match solver {
+            SolverArg::Heuristic => Solver::Heuristic,
+            SolverArg::Exact => Solver::Exact,
+        }
+    }
+}
+
+impl Overrides {
+    pub fn apply(&self, world: &mut input::World) {
+        if let Some(value) = self.max_tested_extensions {
+            world.max_tested_extensions = value;
+        }
+        if let Some(value) = self.max_bag_items {
+            world.max_bag_items = value;
+        }
+        if let Some(value) = self.max_results {
+            world.max_results = value;
+        }
+        if self.max_improvement_steps.is_some() {
+            world.max_improvement_steps = self.max_improvement_steps;
+        }
+        if self.time_limit.is_some() {
+            world.time_limit = self.time_limit;
+        }
+        if self.threads.is_some() {
+            world.threads = self.threads;
+        }
+        if let Some(value) = self.solver {
+            world.solver = value.into();
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// A wall-clock budget shared by all search phases
+///
+/// Phases check it regularly and stop gracefully once it is reached, keeping their best paths so
+/// far. It remembers whether that happened, to flag the results as truncated.
+#[derive(Debug)]
+pub struct Deadline {
+    at: Option<Instant>,
+    reached: AtomicBool,
+}
+
+impl Deadline {
+    /// Start counting the given time limit, if any, from now
+    pub fn new(time_limit: Option<Duration>) -> Self {
+        Deadline {
+            at: time_limit.map(|limit| Instant::now() + limit),
+            reached: AtomicBool::new(false),
+        }
+    }
+
+    /// A deadline that is never reached
+    #[cfg(test)]
+    pub fn never() -> Self {
+        Deadline::new(None)
+    }
+
+    /// Whether the search should stop now
+    pub fn is_reached(&self) -> bool {
+        match self.at {
+            Some(at) if Instant::now() >= at => {
+                self.reached.store(true, Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether some phase stopped early because of this deadline
+    pub fn was_reached(&self) -> bool {
+        self.reached.load(Ordering::Relaxed)
+    }
+}
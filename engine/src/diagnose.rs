@@ -0,0 +1,310 @@
+use crate::models::output::{
+    Diagnosis, MissingRide, UnreachedReason, UnreachedSite, WindowConflict,
+};
+use crate::models::*;
+use itertools::Itertools;
+
+/// Explain why no path visits all the must-visit sites, given the sites that the search reached
+pub fn diagnose(world: &World, reached_sites: &SiteSet) -> Diagnosis {
+    let arrivals = world
+        .travelers
+        .iter()
+        .map(|traveler| earliest_arrivals(world, traveler))
+        .collect_vec();
+
+    // The best outcome of any traveler for each must-visit site
+    let outcomes = world
+        .must_visit
+        .iter()
+        .map(|site| {
+            let outcome = world
+                .travelers
+                .iter()
+                .zip(&arrivals)
+                .map(|(traveler, arrivals)| {
+                    outcome(world, traveler, site, arrivals[site.as_usize()])
+                })
+                .max_by_key(|&(reason, arrival)| (rank(reason), arrival.map(std::cmp::Reverse)))
+                .unwrap_or((UnreachedReason::NoRide, None));
+            (site, outcome)
+        })
+        .collect_vec();
+
+    let unreached_sites = outcomes
+        .iter()
+        .filter(|(site, _)| !reached_sites.contains(*site))
+        .map(|&(site, (reason, earliest_arrival))| UnreachedSite {
+            site: world[site].name.clone(),
+            reason,
+            earliest_arrival,
+        })
+        .collect_vec();
+
+    // Only the sites that can be serviced on their own can conflict, in their opening hours or on
+    // their duties
+    let windows = outcomes
+        .iter()
+        .filter_map(|&(site, (reason, arrival))| match (reason, arrival) {
+            (UnreachedReason::NotExplored, Some(arrival)) => Some((site, arrival)),
+            _ => None,
+        })
+        .flat_map(|(site, arrival)| {
+            let opening = (!world[site].opening_hours.is_empty()).then_some(None);
+            let duties = world[site].duties.iter().copied().map(Some);
+            opening
+                .into_iter()
+                .chain(duties)
+                .map(move |duty| (site, duty, arrival))
+        })
+        .collect_vec();
+    let mut window_conflicts = vec![];
+    for (&first, &second) in windows.iter().tuple_combinations() {
+        let (first_site, first_duty, _) = first;
+        let (second_site, second_duty, _) = second;
+        let has_ride = world.ride_matrix.contains(first_site, second_site)
+            || world.ride_matrix.contains(second_site, first_site);
+        if first_site != second_site
+            && has_ride
+            && !can_follow(world, first, second)
+            && !can_follow(world, second, first)
+        {
+            window_conflicts.push(WindowConflict {
+                first: world[first_site].name.clone(),
+                first_duty,
+                second: world[second_site].name.clone(),
+                second_duty,
+            });
+        }
+    }
+
+    // A ride from any site that a traveler can get to would connect the sites without rides
+    let origins = world
+        .travelers
+        .iter()
+        .flat_map(|traveler| traveler.departure_sites().iter().copied())
+        .chain(world.must_visit.iter())
+        .filter(|site| {
+            arrivals
+                .iter()
+                .any(|arrivals| arrivals[site.as_usize()].is_some())
+        })
+        .sorted()
+        .dedup()
+        .collect_vec();
+    let mut missing_rides = vec![];
+    for &(site, (reason, _)) in &outcomes {
+        if reached_sites.contains(site) || reason != UnreachedReason::NoRide {
+            continue;
+        }
+        for &from in &origins {
+            if from != site && !world.ride_matrix.contains(from, site) {
+                missing_rides.push(MissingRide {
+                    from: world[from].name.clone(),
+                    to: world[site].name.clone(),
+                });
+            }
+        }
+    }
+
+    Diagnosis {
+        unreached_sites,
+        window_conflicts,
+        missing_rides,
+    }
+}
+
+/// The earliest arrival of the traveler at each site, ignoring the services and time windows on
+/// the way, so that it is a lower bound of any real arrival
+fn earliest_arrivals(world: &World, traveler: &Traveler) -> Vec<Option<Timestamp>> {
+    let mut arrivals = vec![None; world.sites.len()];
+    let mut settled = vec![false; world.sites.len()];
+    for &site in traveler.departure_sites() {
+        arrivals[site.as_usize()] = Some(traveler.departure_at());
+    }
+
+    // Dijkstra's algorithm, which also holds for time-dependent rides, since departing later never
+    // arrives sooner
+    while let Some((from, depart_at)) = arrivals
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| !settled[i])
+        .filter_map(|(i, arrival)| Some((SiteId::from_usize(i), (*arrival)?)))
+        .min_by_key(|&(_, arrival)| arrival)
+    {
+        settled[from.as_usize()] = true;
+        // Sites that are never visited can not be passed through
+        if world[from].visit == Visit::Never && !traveler.departure_sites().contains(&from) {
+            continue;
+        }
+        for to in &world.sites {
+            if let Some(ride) = world.ride(from, to.id, depart_at) {
+                let arrival = &mut arrivals[to.id.as_usize()];
+                if arrival.is_none_or(|arrival| depart_at + ride < arrival) {
+                    *arrival = Some(depart_at + ride);
+                }
+            }
+        }
+    }
+
+    arrivals
+}
+
+/// The earliest service start at the site on the given duty, or in its opening hours
+fn earliest_service_on(
+    site: &Site,
+    duty: Option<BoundedTimeWindow>,
+    arrival: Timestamp,
+) -> Option<Timestamp> {
+    match duty {
+        Some(duty) => (arrival <= duty.start()).then_some(duty.start()),
+        None if site.opening_hours.is_empty() => Some(arrival),
+        None => site
+            .opening_after(arrival)
+            .map(|window| window.start().max(arrival)),
+    }
+}
+
+/// The earliest service start at the site, in an opening window or on a duty
+fn earliest_service(site: &Site, arrival: Timestamp) -> Option<Timestamp> {
+    std::iter::once(None)
+        .chain(site.duties.iter().copied().map(Some))
+        .filter_map(|duty| earliest_service_on(site, duty, arrival))
+        .min()
+}
+
+fn outcome(
+    world: &World,
+    traveler: &Traveler,
+    site: SiteId,
+    arrival: Option<Timestamp>,
+) -> (UnreachedReason, Option<Timestamp>) {
+    let arrival = match arrival {
+        None => return (UnreachedReason::NoRide, None),
+        Some(arrival) => arrival,
+    };
+    let site = &world[site];
+    let reason = match earliest_service(site, arrival) {
+        None => UnreachedReason::Closed,
+        Some(start) => match traveler.max_end_at {
+            Some(max_end_at) if start + site.service_time > max_end_at => UnreachedReason::TooLate,
+            _ => UnreachedReason::NotExplored,
+        },
+    };
+    (reason, Some(arrival))
+}
+
+/// How close the reason is to a successful visit
+fn rank(reason: UnreachedReason) -> i32 {
+    match reason {
+        UnreachedReason::NoRide => 0,
+        UnreachedReason::Closed => 1,
+        UnreachedReason::TooLate => 2,
+        UnreachedReason::NotExplored => 3,
+    }
+}
+
+/// A site, one of its duties or none for its opening hours, and the earliest arrival there
+type Window = (SiteId, Option<BoundedTimeWindow>, Timestamp);
+
+/// Whether `second` can be serviced right after `first`, in their windows
+fn can_follow(world: &World, first: Window, second: Window) -> bool {
+    let (first, first_duty, first_arrival) = first;
+    let (second, second_duty, _) = second;
+    let service_end = match earliest_service_on(&world[first], first_duty, first_arrival) {
+        None => return false,
+        Some(start) => start + world[first].service_time,
+    };
+    match world.ride(first, second, service_end) {
+        None => false,
+        Some(ride) => {
+            earliest_service_on(&world[second], second_duty, service_end + ride).is_some()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explain_failure() {
+        let mut home = Site::mock();
+        home.visit = Visit::Never;
+        let mut morning = Site::mock();
+        morning.service_time = Duration::from(10);
+        morning.opening_hours = vec![BoundedTimeWindow::from((0, 10))];
+        let mut also_morning = morning.clone();
+        also_morning.opening_hours = vec![BoundedTimeWindow::from((5, 15))];
+        let mut closed = Site::mock();
+        closed.opening_hours = vec![BoundedTimeWindow::from((0, 1))];
+        let mut on_duty = Site::mock();
+        on_duty.service_time = Duration::from(10);
+        on_duty.duties = vec![BoundedTimeWindow::from((2, 12))];
+        let sites = vec![home, morning, also_morning, closed, Site::mock(), on_duty];
+        let mut world = World::mock(sites);
+        world.travelers[0].min_start_at = Timestamp::from(0);
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.must_visit = (1..6).map(SiteId::from_usize).collect();
+        let rides = [
+            (0, 1, 0),
+            (0, 2, 0),
+            (1, 2, 1),
+            (2, 1, 1),
+            (0, 3, 5),
+            (0, 5, 0),
+            (5, 1, 1),
+        ];
+        for (from, to, ride) in rides {
+            world.ride_matrix.set(
+                SiteId::from_usize(from),
+                SiteId::from_usize(to),
+                Duration::from(ride),
+            );
+        }
+
+        let diagnosis = diagnose(&world, &[1].into_iter().map(SiteId::from).collect());
+        let unreached = diagnosis
+            .unreached_sites
+            .iter()
+            .map(|site| (site.site.as_str(), site.reason))
+            .collect_vec();
+        assert_eq!(
+            unreached,
+            vec![
+                ("2", UnreachedReason::NotExplored),
+                ("3", UnreachedReason::Closed),
+                ("4", UnreachedReason::NoRide),
+                ("5", UnreachedReason::NotExplored)
+            ]
+        );
+        let conflicts = diagnosis
+            .window_conflicts
+            .iter()
+            .map(|conflict| {
+                (
+                    conflict.first.as_str(),
+                    conflict.first_duty,
+                    conflict.second.as_str(),
+                    conflict.second_duty,
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            conflicts,
+            vec![
+                ("1", None, "2", None),
+                ("1", None, "5", Some(BoundedTimeWindow::from((2, 12))))
+            ]
+        );
+        // Only the rides to the site without rides, from the sites that can be reached
+        let missing_rides = diagnosis
+            .missing_rides
+            .iter()
+            .map(|ride| (ride.from.as_str(), ride.to.as_str()))
+            .collect_vec();
+        assert_eq!(
+            missing_rides,
+            vec![("0", "4"), ("1", "4"), ("2", "4"), ("3", "4"), ("5", "4")]
+        );
+    }
+}
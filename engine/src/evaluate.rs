@@ -0,0 +1,346 @@
+use crate::models::output::Violation;
+use crate::models::*;
+use anyhow::{bail, ensure, Result};
+use itertools::Itertools;
+use std::sync::Arc;
+
+/// Schedule the given itinerary, explaining the first reason why it is not feasible. Fail if the
+/// itinerary itself is invalid, like naming an unknown site
+pub fn evaluate(world: &World, itinerary: &input::Itinerary) -> Result<output::Evaluation> {
+    let routes = resolve(world, itinerary)?;
+    Ok(match schedule(world, &routes) {
+        Ok(path) => output::Evaluation {
+            path: Some(output::Path::new(world, &path, false)),
+            violation: None,
+        },
+        Err(violation) => output::Evaluation {
+            path: None,
+            violation: Some(violation),
+        },
+    })
+}
+
+/// A route of the itinerary, with the names resolved
+struct Route<'a> {
+    traveler: &'a Traveler,
+    start_in: SiteId,
+    stops: Vec<StopSketch>,
+}
+
+fn resolve<'a>(world: &'a World, itinerary: &input::Itinerary) -> Result<Vec<Route<'a>>> {
+    let mut routes = vec![];
+    let mut last_traveler = None;
+    let mut visited_sites = SiteSet::new();
+
+    for (i, route) in itinerary.routes.iter().enumerate() {
+        let traveler = match &route.traveler {
+            Some(name) => world.find_traveler(name)?,
+            None if world.travelers.len() == 1 => &world.travelers[0],
+            None => bail!("Route {} must name its traveler", i),
+        };
+        ensure!(
+            last_traveler < Some(traveler.id),
+            "Route {} must come after the route of traveler {}",
+            i,
+            traveler.name
+        );
+        last_traveler = Some(traveler.id);
+        ensure!(
+            traveler.end != PathEnd::EndSite || !traveler.end_sites.is_empty(),
+            "{} has no end site",
+            traveler.name
+        );
+
+        let start_in = world.find_site(&route.start_in)?.id;
+        ensure!(
+            traveler.departure_sites().contains(&start_in),
+            "{} can not start in {}",
+            traveler.name,
+            route.start_in
+        );
+        let mut stops = vec![];
+        for stop in &route.stops {
+            let site = world.find_site(&stop.site)?;
+            ensure!(
+                site.visit != Visit::Never,
+                "Site {} is never visited",
+                site.name
+            );
+            ensure!(
+                visited_sites.insert(site.id),
+                "Site {} is visited more than once",
+                site.name
+            );
+            ensure!(
+                !world.completed_sites.contains(site.id),
+                "Site {} was completed before re-planning",
+                site.name
+            );
+            if let Some(duty) = stop.duty {
+                ensure!(
+                    site.duties.contains(&duty),
+                    "Site {} has no duty {:?}",
+                    site.name,
+                    duty
+                );
+            }
+            stops.push(StopSketch {
+                site: site.id,
+                duty: stop.duty,
+                kind: StopKind::Visit,
+            });
+        }
+
+        routes.push(Route {
+            traveler,
+            start_in,
+            stops,
+        });
+    }
+
+    ensure!(!routes.is_empty(), "The itinerary has no route");
+    Ok(routes)
+}
+
+fn schedule(world: &World, routes: &[Route]) -> Result<Path, Violation> {
+    let mut previous: Option<Arc<Path>> = None;
+
+    for route in routes {
+        let path = schedule_route(world, route.traveler, route.start_in, &route.stops)?;
+        let closed_path = close_route(world, route, path)?;
+        previous = Some(Arc::new(closed_path.after(previous)));
+    }
+
+    let path = previous.expect("resolve() checks that there is a route");
+    let unvisited = world
+        .must_visit
+        .iter()
+        .filter(|&site| !path.visited_sites.contains(site))
+        .collect_vec();
+    if !unvisited.is_empty() {
+        return Err(Violation::Unvisited {
+            sites: unvisited
+                .into_iter()
+                .map(|site| world[site].name.clone())
+                .collect(),
+        });
+    }
+    Ok(Path::clone(&path))
+}
+
+/// Make the route end where its traveler is allowed to, riding to the end site that is reached
+/// the soonest. When no end site can be reached, explain why the first one can not
+fn close_route(world: &World, route: &Route, path: Path) -> Result<Path, Violation> {
+    let traveler = route.traveler;
+    let end_sites = match traveler.end {
+        PathEnd::Anywhere => return Ok(path),
+        PathEnd::EndSite => &traveler.end_sites[..],
+        PathEnd::Start => &[traveler.return_site(route.start_in)],
+    };
+    if end_sites.contains(&path.end().0) {
+        return Ok(path);
+    }
+
+    let mut stops = route.stops.clone();
+    let mut closed_paths = vec![];
+    for &end_site in end_sites {
+        stops.push(StopSketch {
+            site: end_site,
+            duty: None,
+            kind: StopKind::Return,
+        });
+        closed_paths.push(schedule_route(world, traveler, route.start_in, &stops));
+        stops.pop();
+    }
+    let soonest = closed_paths
+        .iter()
+        .flatten()
+        .min_by_key(|path| path.end().1)
+        .cloned();
+    match soonest {
+        Some(path) => Ok(path),
+        None => closed_paths
+            .into_iter()
+            .next()
+            .expect("resolve() checks that there is an end site"),
+    }
+}
+
+fn schedule_route(
+    world: &World,
+    traveler: &Traveler,
+    start_in: SiteId,
+    stops: &[StopSketch],
+) -> Result<Path, Violation> {
+    let site_name = |stop: usize| world[stops[stop].site].name.clone();
+    let path =
+        Path::schedule(world, traveler.id, start_in, stops).map_err(|error| match error {
+            ScheduleError::MissingRide { stop } => Violation::MissingRide {
+                traveler: traveler.name.clone(),
+                from: match stop {
+                    0 => world[start_in].name.clone(),
+                    _ => site_name(stop - 1),
+                },
+                to: site_name(stop),
+            },
+            ScheduleError::Closed { stop, ready_at } => Violation::Closed {
+                traveler: traveler.name.clone(),
+                site: site_name(stop),
+                ready_at,
+            },
+            ScheduleError::WindowMissed {
+                stop,
+                window,
+                service_end,
+            } => Violation::WindowMissed {
+                traveler: traveler.name.clone(),
+                site: site_name(stop),
+                window,
+                service_end,
+            },
+            ScheduleError::TooMuchWork { stop } => Violation::TooMuchWork {
+                traveler: traveler.name.clone(),
+                site: site_name(stop),
+            },
+            ScheduleError::PrecedenceBroken { stop, before } => Violation::PrecedenceBroken {
+                traveler: traveler.name.clone(),
+                before: world[before].name.clone(),
+                after: site_name(stop),
+            },
+        })?;
+
+    match traveler.max_end_at {
+        Some(max_end_at) if path.end().1 > max_end_at => Err(Violation::LateEnd {
+            traveler: traveler.name.clone(),
+            end_at: path.end().1,
+            max_end_at,
+        }),
+        _ => Ok(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn itinerary(stops: &[(&str, Option<(i32, i32)>)]) -> input::Itinerary {
+        input::Itinerary {
+            routes: vec![input::ItineraryRoute {
+                traveler: None,
+                start_in: "0".to_string(),
+                stops: stops
+                    .iter()
+                    .map(|&(site, duty)| input::ItineraryStop {
+                        site: site.to_string(),
+                        duty: duty.map(BoundedTimeWindow::from),
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn evaluate_itinerary() {
+        let mut site1 = Site::mock();
+        site1.duties = vec![BoundedTimeWindow::from((10, 20))];
+        let mut world = World::mock(vec![Site::mock(), site1, Site::mock()]);
+        world.travelers[0].min_start_at = Timestamp::from(0);
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(5));
+        world
+            .ride_matrix
+            .set(SiteId::from(1), SiteId::from(2), Duration::from(5));
+
+        let evaluation =
+            evaluate(&world, &itinerary(&[("1", Some((10, 20))), ("2", None)])).unwrap();
+        let path = evaluation.path.unwrap();
+        assert_eq!(path.routes[0].stops.len(), 2);
+        assert_eq!(path.cost.total_ride, Duration::from(10));
+
+        // Missing ride
+        let violation = evaluate(&world, &itinerary(&[("2", None)]))
+            .unwrap()
+            .violation;
+        assert!(matches!(
+            violation,
+            Some(Violation::MissingRide { from, to, .. }) if from == "0" && to == "2"
+        ));
+        // Too late for the duty
+        world.travelers[0].min_start_at = Timestamp::from(20);
+        let violation = evaluate(&world, &itinerary(&[("1", Some((10, 20)))]))
+            .unwrap()
+            .violation;
+        assert!(matches!(violation, Some(Violation::WindowMissed { .. })));
+        // Ends too late
+        world.travelers[0].max_end_at = Some(Timestamp::from(22));
+        let violation = evaluate(&world, &itinerary(&[("1", None)]))
+            .unwrap()
+            .violation;
+        assert!(matches!(violation, Some(Violation::LateEnd { .. })));
+        // Unknown duty
+        assert!(evaluate(&world, &itinerary(&[("1", Some((0, 20)))])).is_err());
+        // Visited twice
+        assert!(evaluate(&world, &itinerary(&[("1", None), ("1", None)])).is_err());
+        // Never visited
+        world.sites[2].visit = Visit::Never;
+        assert!(evaluate(&world, &itinerary(&[("2", None)])).is_err());
+    }
+
+    #[test]
+    fn return_to_start() {
+        let mut world = World::mock(vec![Site::mock(), Site::mock()]);
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.travelers[0].end = PathEnd::Start;
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(5));
+
+        let violation = evaluate(&world, &itinerary(&[("1", None)]))
+            .unwrap()
+            .violation;
+        assert!(matches!(
+            violation,
+            Some(Violation::MissingRide { from, to, .. }) if from == "1" && to == "0"
+        ));
+
+        world
+            .ride_matrix
+            .set(SiteId::from(1), SiteId::from(0), Duration::from(5));
+        let path = evaluate(&world, &itinerary(&[("1", None)]))
+            .unwrap()
+            .path
+            .unwrap();
+        assert_eq!(path.routes[0].stops.len(), 2);
+        assert_eq!(path.cost.total_ride, Duration::from(10));
+    }
+
+    #[test]
+    fn evaluate_replan() {
+        let mut world = World::mock(vec![Site::mock(), Site::mock(), Site::mock()]);
+        world.must_visit = [2].into_iter().map(SiteId::from).collect();
+        world.completed_sites = [SiteId::from(1)].into_iter().collect();
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.travelers[0].current = Some(CurrentPosition {
+            site: SiteId::from(1),
+            at: Timestamp::from(50),
+            started_in: SiteId::from(0),
+            work_since_break: Duration::ZERO,
+            total_work: Duration::ZERO,
+        });
+        world
+            .ride_matrix
+            .set(SiteId::from(1), SiteId::from(2), Duration::from(5));
+
+        let mut itinerary = itinerary(&[("2", None)]);
+        itinerary.routes[0].start_in = "1".to_string();
+        let evaluation = evaluate(&world, &itinerary).unwrap();
+        assert!(evaluation.violation.is_none());
+        assert_eq!(evaluation.path.unwrap().cost.total_ride, Duration::from(5));
+
+        // Completed sites are not visited again
+        itinerary.routes[0].stops[0].site = "1".to_string();
+        assert!(evaluate(&world, &itinerary).is_err());
+    }
+}
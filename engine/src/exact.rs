@@ -0,0 +1,134 @@
+use crate::build::{close_if_complete, extend_paths, seed_paths, Built, Progress};
+use crate::deadline::Deadline;
+use crate::labels::Labels;
+use crate::models::output::Phase;
+use crate::models::*;
+use crate::path_bag::PathBag;
+use itertools::Itertools;
+
+/// Builds the true Pareto front of the finished paths
+///
+/// Every partial path is extended with every possible stop, only discarding the ones dominated by
+/// a path in the same state. This is exponential on the number of sites. If the deadline is
+/// reached, the result is only the front of the paths finished so far. The progress is reported
+/// after each iteration
+pub fn build(world: &World, deadline: &Deadline, on_progress: &mut impl FnMut(Progress)) -> Built {
+    let mut labels = Labels::new(world);
+    let mut finished_paths = PathBag::unbounded(world.objectives.clone());
+    let mut reached_sites = SiteSet::new();
+
+    for path in seed_paths(world) {
+        labels.add(path);
+    }
+
+    let mut pruned = 0;
+    let mut iteration = 0;
+    while !labels.is_empty() && !deadline.is_reached() {
+        log::info!("Exact iteration starting from {} labels", labels.len());
+        pruned += labels.pruned;
+        let mut new_labels = Labels::new(world);
+        let base_paths = labels.into_paths().collect_vec();
+        for (extended_paths, closed_paths) in extend_paths(world, deadline, &base_paths, usize::MAX)
+        {
+            for path in extended_paths {
+                reached_sites.union_with(&path.visited_sites);
+                new_labels.add(path);
+            }
+            for closed_path in closed_paths {
+                finished_paths.add(closed_path);
+            }
+        }
+        labels = new_labels;
+        iteration += 1;
+        on_progress(Progress {
+            phase: Phase::Exact,
+            iteration,
+            pending: labels.len(),
+            base_paths: None,
+            finished_paths: &finished_paths,
+            pruned: labels.pruned,
+        });
+    }
+    pruned += labels.pruned;
+    log::info!("Exact search pruned {} dominated labels", pruned);
+
+    // The deadline was reached: keep the complete paths that were not closed yet
+    for base_path in labels.into_paths() {
+        for closed_path in close_if_complete(world, &base_path) {
+            finished_paths.add(closed_path);
+        }
+    }
+
+    Built {
+        paths: finished_paths.into_pareto_front(),
+        reached_sites,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build;
+
+    /// Build a world where always going to the closest site first is a trap
+    fn greedy_trap() -> World {
+        let mut home = Site::mock();
+        home.visit = Visit::Never;
+        let mut world = World::mock(vec![home, Site::mock(), Site::mock(), Site::mock()]);
+        world.travelers[0].min_start_at = Timestamp::from(0);
+        world.travelers[0].start_sites = vec![SiteId::from(0)];
+        world.must_visit = (1..4).map(SiteId::from_usize).collect();
+        world.max_tested_extensions = 1;
+        world.max_bag_items = 10;
+        for (from, to, ride) in [
+            (0, 1, 1),
+            (0, 2, 2),
+            (0, 3, 3),
+            (1, 2, 100),
+            (1, 3, 1),
+            (2, 1, 1),
+            (2, 3, 100),
+            (3, 1, 100),
+            (3, 2, 100),
+        ] {
+            world.ride_matrix.set(
+                SiteId::from_usize(from),
+                SiteId::from_usize(to),
+                Duration::from(ride),
+            );
+        }
+        world
+    }
+
+    fn best_ride(bag: PathBag) -> Option<Duration> {
+        bag.into_paths().map(|path| path.cost.total_ride).min()
+    }
+
+    #[test]
+    fn heuristic_gap() {
+        let world = greedy_trap();
+        assert_eq!(
+            best_ride(build::build(&world, &Deadline::never(), &mut |_| {}).paths),
+            Some(Duration::from(102))
+        );
+        assert_eq!(
+            best_ride(build(&world, &Deadline::never(), &mut |_| {}).paths),
+            Some(Duration::from(4))
+        );
+
+        // The exact front does not depend on the heuristic parameters
+        let mut wide_world = greedy_trap();
+        wide_world.max_tested_extensions = 10;
+        let exact = build(&world, &Deadline::never(), &mut |_| {})
+            .paths
+            .into_paths()
+            .map(|path| path.cost)
+            .collect::<Vec<_>>();
+        let wide_exact = build(&wide_world, &Deadline::never(), &mut |_| {})
+            .paths
+            .into_paths()
+            .map(|path| path.cost)
+            .collect::<Vec<_>>();
+        assert_eq!(exact, wide_exact);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::build::{close_path, ends_in_time, Progress};
+use crate::deadline::Deadline;
+use crate::models::output::Phase;
+use crate::models::*;
+use crate::path_bag::PathBag;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+/// The longest segment of stops moved at once by or-opt
+const MAX_MOVED_SEGMENT: usize = 3;
+
+/// Improve each finished path by rearranging the stops of its routes, adding the improved paths
+/// to the bag. Paths are left as they are once the deadline is reached. The progress is reported
+/// after each batch of paths, one per thread
+pub fn improve(
+    world: &World,
+    deadline: &Deadline,
+    paths: PathBag,
+    on_progress: &mut impl FnMut(Progress),
+) -> PathBag {
+    let paths = paths.into_paths().collect_vec();
+    let mut improved_paths = PathBag::new(world.max_bag_items, world.objectives.clone());
+    for path in &paths {
+        improved_paths.add(path.clone());
+    }
+    let mut improved = 0;
+
+    // Improve each batch in parallel, then merge in the original order
+    let batch_size = rayon::current_num_threads().max(1);
+    for (i, batch) in paths.chunks(batch_size).enumerate() {
+        let better_paths = batch
+            .par_iter()
+            .map(|path| improve_path(world, deadline, path))
+            .collect::<Vec<_>>();
+        for better_path in better_paths.into_iter().flatten() {
+            improved += 1;
+            improved_paths.add(better_path);
+        }
+        on_progress(Progress {
+            phase: Phase::Improve,
+            iteration: i + 1,
+            pending: paths.len().saturating_sub((i + 1) * batch_size),
+            base_paths: None,
+            finished_paths: &improved_paths,
+            pruned: 0,
+        });
+    }
+    log::info!("Local search improved {} paths", improved);
+
+    improved_paths
+}
+
+/// Apply moves that make the path strictly better, until no move does or the maximum number of
+/// steps or the deadline is reached. Return `None` if no move improved the path
+fn improve_path(world: &World, deadline: &Deadline, path: &Path) -> Option<Path> {
+    let mut current = None;
+    for _ in 0..world.max_improvement_steps {
+        if deadline.is_reached() {
+            break;
+        }
+        match improving_move(world, current.as_ref().unwrap_or(path)) {
+            None => break,
+            Some(better_path) => current = Some(better_path),
+        }
+    }
+    current
+}
+
+/// Return the first neighbour that dominates the given path. Each route is changed on its own,
+/// so sites never move between travelers
+fn improving_move(world: &World, path: &Path) -> Option<Path> {
+    let routes = path.routes().collect_vec();
+
+    for (i, route) in routes.iter().enumerate() {
+        let visits = route
+            .stops
+            .iter()
+            .filter(|stop| stop.kind == StopKind::Visit)
+            .map(Stop::sketch)
+            .collect_vec();
+
+        for candidate in neighbours(world, &visits) {
+            let new_route =
+                match Path::try_schedule(world, route.traveler, route.start_in, &candidate) {
+                    Some(new_route) if ends_in_time(world, &new_route) => new_route,
+                    _ => continue,
+                };
+
+            for closed_route in close_path(world, &new_route.after(route.previous.clone())) {
+                // Chain the routes of the next travelers again
+                let new_path = routes[..i]
+                    .iter()
+                    .rev()
+                    .fold(closed_route, |previous, &next| {
+                        Path::clone(next).after(Some(Arc::new(previous)))
+                    });
+                if world.objectives.compare(&new_path.cost, &path.cost) == Some(Ordering::Less) {
+                    return Some(new_path);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Return the stop sequences obtained by 2-opt, or-opt, relocate, swap and duty re-selection moves
+fn neighbours(world: &World, stops: &[StopSketch]) -> Vec<Vec<StopSketch>> {
+    let mut result = vec![];
+    let n = stops.len();
+
+    // 2-opt: reverse a segment
+    for i in 0..n {
+        for j in i + 2..n {
+            let mut candidate = stops.to_vec();
+            candidate[i..=j].reverse();
+            result.push(candidate);
+        }
+    }
+
+    // Or-opt: move a segment to another position. Relocate is the single stop case
+    for len in 1..=MAX_MOVED_SEGMENT.min(n) {
+        for i in 0..=n - len {
+            let mut rest = stops.to_vec();
+            let segment = rest.drain(i..i + len).collect_vec();
+            for j in 0..=rest.len() {
+                if j != i {
+                    let mut candidate = rest.clone();
+                    candidate.splice(j..j, segment.iter().copied());
+                    result.push(candidate);
+                }
+            }
+        }
+    }
+
+    // Swap two stops. Adjacent stops are already covered by relocate
+    for i in 0..n {
+        for j in i + 2..n {
+            let mut candidate = stops.to_vec();
+            candidate.swap(i, j);
+            result.push(candidate);
+        }
+    }
+
+    // Pick another duty or no duty at all
+    for (i, stop) in stops.iter().enumerate() {
+        let duties = world[stop.site].duties.iter().copied().map(Some);
+        for duty in std::iter::once(None).chain(duties) {
+            if duty != stop.duty {
+                let mut candidate = stops.to_vec();
+                candidate[i].duty = duty;
+                result.push(candidate);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fix_early_ordering() {
+        let mut home = Site::mock();
+        home.visit = Visit::Never;
+        let mut world = World::mock(vec![home, Site::mock(), Site::mock(), Site::mock()]);
+        world.travelers[0].min_start_at = Timestamp::from(0);
+        world.max_improvement_steps = 10;
+        for (from, to, ride) in [(0, 1, 1), (0, 2, 2), (1, 3, 1), (2, 1, 1), (3, 2, 100)] {
+            world.ride_matrix.set(
+                SiteId::from_usize(from),
+                SiteId::from_usize(to),
+                Duration::from(ride),
+            );
+        }
+
+        let stops = [1, 3, 2].map(|site| StopSketch {
+            site: SiteId::from(site),
+            duty: None,
+            kind: StopKind::Visit,
+        });
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        assert_eq!(path.cost.total_ride, Duration::from(102));
+
+        let improved = improve_path(&world, &Deadline::never(), &path).unwrap();
+        assert_eq!(improved.cost.total_ride, Duration::from(4));
+        assert_eq!(
+            improved.stops.iter().map(|stop| stop.site).collect_vec(),
+            vec![SiteId::from(2), SiteId::from(1), SiteId::from(3)]
+        );
+        assert!(improve_path(&world, &Deadline::never(), &improved).is_none());
+    }
+}
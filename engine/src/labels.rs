@@ -0,0 +1,244 @@
+use crate::models::*;
+use itertools::Itertools;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Represents a set of partial paths, where no path is dominated by another one that can be
+/// extended in exactly the same ways
+///
+/// Paths are grouped by their state: the traveler, the visited sites, the end site, the start site
+/// when routes return to it and, with precedences, the visits of the route that later visits
+/// depend on. Inside a group, a path dominates another when it has no worse cost and no worse
+/// timing: it ends no later, it starts no earlier, it can be delayed at least as much and it
+/// worked no more.
+#[derive(Debug, Clone)]
+pub struct Labels {
+    groups: Vec<Vec<Path>>,
+    group_by_state: HashMap<LabelState, usize>,
+    objectives: Objectives,
+    precedences: Vec<Precedence>,
+    /// Whether the routes must return to their start site
+    by_start: bool,
+    len: usize,
+    /// How many paths were rejected or removed because they were dominated
+    pub pruned: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LabelState {
+    traveler: TravelerId,
+    visited_sites: SiteSet,
+    end_in: SiteId,
+    start_in: Option<SiteId>,
+    /// The sites of the route that some precedence starts from
+    precedence_sites: SiteSet,
+}
+
+impl Labels {
+    pub fn new(world: &World) -> Self {
+        Labels {
+            groups: vec![],
+            group_by_state: HashMap::new(),
+            objectives: world.objectives.clone(),
+            precedences: world.precedences.clone(),
+            by_start: world
+                .travelers
+                .iter()
+                .any(|traveler| traveler.end == PathEnd::Start),
+            len: 0,
+            pruned: 0,
+        }
+    }
+
+    /// Add the path, unless it is dominated. Return whether it was added
+    pub fn add(&mut self, path: Path) -> bool {
+        let state = LabelState {
+            traveler: path.traveler,
+            visited_sites: path.visited_sites.clone(),
+            end_in: path.end().0,
+            start_in: self.by_start.then_some(path.start_in),
+            precedence_sites: path
+                .stops
+                .iter()
+                .map(|stop| stop.site)
+                .filter(|&site| {
+                    self.precedences
+                        .iter()
+                        .any(|precedence| precedence.before == site)
+                })
+                .collect(),
+        };
+        let groups = &mut self.groups;
+        let index = *self.group_by_state.entry(state).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        let group = &mut self.groups[index];
+
+        if group
+            .iter()
+            .any(|label| dominates(&self.objectives, &self.precedences, label, &path))
+        {
+            self.pruned += 1;
+            return false;
+        }
+
+        let len_before = group.len();
+        group.retain(|label| !dominates(&self.objectives, &self.precedences, &path, label));
+        let removed = len_before - group.len();
+        group.push(path);
+        self.pruned += removed;
+        self.len = self.len + 1 - removed;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the paths in a deterministic order
+    pub fn into_paths(self) -> impl Iterator<Item = Path> {
+        self.groups.into_iter().flatten()
+    }
+}
+
+fn dominates(objectives: &Objectives, precedences: &[Precedence], a: &Path, b: &Path) -> bool {
+    matches!(
+        objectives.compare(&a.cost, &b.cost),
+        Some(Ordering::Less | Ordering::Equal)
+    ) && a.end().1 <= b.end().1
+        && a.start_at >= b.start_at
+        && latest_start(a) >= latest_start(b)
+        && a.work_since_break <= b.work_since_break
+        && a.total_work <= b.total_work
+        && timed_ends(precedences, a)
+            .zip(timed_ends(precedences, b))
+            .all(|(a_end, b_end)| a_end >= b_end)
+}
+
+/// The end of the services that timed precedences start from, by site. A later end leaves more
+/// time for the next visits
+fn timed_ends<'a>(
+    precedences: &'a [Precedence],
+    path: &'a Path,
+) -> impl Iterator<Item = Timestamp> + 'a {
+    path.stops
+        .iter()
+        .filter(|stop| {
+            precedences
+                .iter()
+                .any(|precedence| precedence.before == stop.site && precedence.within.is_some())
+        })
+        .sorted_by_key(|stop| stop.site)
+        .map(|stop| stop.service_end)
+}
+
+fn latest_start(path: &Path) -> CappedMax<Timestamp> {
+    match path.slack {
+        CappedMax::Max => CappedMax::Max,
+        CappedMax::Value(slack) => CappedMax::Value(path.start_at + slack),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_ending_at(end_in: usize, end_at: i32, total_ride: i32) -> Path {
+        let mut path = Path::mock();
+        path.start_in = SiteId::from_usize(end_in);
+        path.start_at = Timestamp::from(end_at);
+        path.cost.total_ride = Duration::from(total_ride);
+        path
+    }
+
+    #[test]
+    fn prune_same_state() {
+        let mut labels = Labels::new(&World::mock(vec![]));
+        assert!(labels.add(path_ending_at(0, 10, 5)));
+        // Same state and no better
+        assert!(!labels.add(path_ending_at(0, 10, 6)));
+        // Different end site
+        assert!(labels.add(path_ending_at(1, 10, 6)));
+        // Same state, but cheaper
+        assert!(labels.add(path_ending_at(0, 10, 4)));
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.pruned, 2);
+    }
+
+    #[test]
+    fn keep_start_sites_apart() {
+        let stops = [StopSketch {
+            site: SiteId::from(2),
+            duty: None,
+            kind: StopKind::Visit,
+        }];
+        let mut world = World::mock((0..3).map(|_| Site::mock()).collect());
+        for from in 0..2 {
+            world.ride_matrix.set(
+                SiteId::from_usize(from),
+                SiteId::from(2),
+                Duration::from(10),
+            );
+        }
+        let schedule = |world: &World, start_in| {
+            Path::try_schedule(world, TravelerId::from(0), SiteId::from(start_in), &stops).unwrap()
+        };
+
+        // The paths only differ by their start site, which does not matter when ending anywhere
+        let mut labels = Labels::new(&world);
+        assert!(labels.add(schedule(&world, 0)));
+        assert!(!labels.add(schedule(&world, 1)));
+
+        // But they return to different sites
+        world.travelers[0].end = PathEnd::Start;
+        let mut labels = Labels::new(&world);
+        assert!(labels.add(schedule(&world, 0)));
+        assert!(labels.add(schedule(&world, 1)));
+    }
+
+    #[test]
+    fn compare_timed_precedences() {
+        let mut world = World::mock((0..3).map(|_| Site::mock()).collect());
+        for from in 0..3 {
+            for to in 0..3 {
+                world.ride_matrix.set(
+                    SiteId::from_usize(from),
+                    SiteId::from_usize(to),
+                    Duration::from(10),
+                );
+            }
+        }
+        world.precedences = vec![Precedence {
+            before: SiteId::from(1),
+            after: SiteId::from(0),
+            immediately: false,
+            within: Some(Duration::from(100)),
+        }];
+        let stops = [1, 2].map(|site| StopSketch {
+            site: SiteId::from_usize(site),
+            duty: None,
+            kind: StopKind::Visit,
+        });
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        let with_end = |end| {
+            let mut path = path.clone();
+            path.stops[0].service_end = Timestamp::from(end);
+            path
+        };
+        let (path, later, sooner) = (with_end(5), with_end(6), with_end(4));
+
+        // The paths share their state, so a later end of the first service dominates
+        let mut labels = Labels::new(&world);
+        assert!(labels.add(path));
+        assert!(labels.add(later));
+        assert!(!labels.add(sooner));
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels.pruned, 2);
+    }
+}
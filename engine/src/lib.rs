@@ -0,0 +1,86 @@
+//! Plans the routes of travelers that must visit a set of sites
+//!
+//! The usual flow is to deserialize an [`input::World`], convert it with
+//! [`World::try_from_json`] and call [`solve`], whose [`output::Answer`] can be serialized back.
+//!
+//! ```
+//! use traveling_simon::{input, solve, World};
+//!
+//! let world_input: input::World = serde_json::from_str(
+//!     r#"{
+//!         "sites": [
+//!             {"name": "home", "rideDurations": {"shop": "10m"}, "duties": [],
+//!              "serviceTime": "0s", "canStartHere": true, "visit": "NEVER"},
+//!             {"name": "shop", "rideDurations": {}, "duties": [],
+//!              "serviceTime": "15m", "canStartHere": false, "visit": "ALWAYS"}
+//!         ],
+//!         "minStartAt": "09:00",
+//!         "maxEndAt": null,
+//!         "maxTestedExtensions": 10,
+//!         "maxBagItems": 10,
+//!         "maxResults": 3
+//!     }"#,
+//! )
+//! .unwrap();
+//! let world = World::try_from_json(world_input).unwrap();
+//! let answer = solve(&world, None).unwrap();
+//! assert_eq!(answer.paths[0].routes[0].stops[0].site, "shop");
+//! ```
+//!
+//! The search phases themselves are not public: [`solve`] runs them as the world configures, and
+//! with the default settings it only runs the build phase, so it stands for calling it directly.
+
+use crate::deadline::Deadline;
+use crate::models::*;
+use anyhow::{Context, Result};
+
+mod build;
+mod deadline;
+mod diagnose;
+mod evaluate;
+mod exact;
+mod improve;
+mod labels;
+mod models;
+mod parsers;
+mod path_bag;
+mod validate;
+
+pub use evaluate::evaluate;
+pub use models::{input, output, World};
+pub use validate::validate;
+
+/// Solve the world with the configured solver and phases. When given, `on_progress` is called
+/// after each iteration of each phase
+pub fn solve(
+    world: &World,
+    mut on_progress: Option<&mut (dyn FnMut(output::Progress) + Send)>,
+) -> Result<output::Answer> {
+    // The snapshot clones the best paths, so it is only built when someone listens
+    let on_progress = &mut |progress: build::Progress| {
+        if let Some(on_progress) = on_progress.as_mut() {
+            on_progress(output::Progress::new(world, &progress));
+        }
+    };
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(world.threads)
+        .build()
+        .context("failed to start threads")?;
+    let deadline = Deadline::new(world.time_limit);
+    let built = thread_pool.install(|| match world.solver {
+        Solver::Heuristic => {
+            let built = build::build(world, &deadline, on_progress);
+            build::Built {
+                paths: improve::improve(world, &deadline, built.paths, on_progress),
+                reached_sites: built.reached_sites,
+            }
+        }
+        Solver::Exact => exact::build(world, &deadline, on_progress),
+    });
+
+    let mut answer = output::Answer::new(world, &built.paths, deadline.was_reached());
+    if answer.paths.is_empty() {
+        answer.diagnosis = Some(diagnose::diagnose(world, &built.reached_sites));
+    }
+    Ok(answer)
+}
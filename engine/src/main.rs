@@ -1,37 +1,274 @@
-use crate::models::*;
-use anyhow::{Context, Result};
-use itertools::Itertools;
-use std::io::Read;
+use crate::cli::{Cli, Command, Format, InputOutput, Overrides};
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use traveling_simon::{evaluate, input, output, solve, validate, World};
 
-mod build;
-mod models;
-mod parsers;
-mod path_bag;
+mod cli;
+mod serve;
 
 fn main() -> Result<()> {
     env_logger::init();
 
-    // Read world description from stdin
-    let mut world_json = String::new();
-    std::io::stdin()
-        .read_to_string(&mut world_json)
-        .context("failed to read stdin")?;
-    let world_input: input::World =
-        serde_json::from_str(&world_json).context("failed to parse stdin")?;
+    match Cli::parse().command {
+        None => run_solve(&InputOutput::default(), &Overrides::default(), false),
+        Some(Command::Solve {
+            io,
+            overrides,
+            stream,
+        }) => run_solve(&io, &overrides, stream),
+        Some(Command::Validate { io }) => run_validate(&io),
+        Some(Command::Evaluate { io, itinerary }) => run_evaluate(&io, &itinerary),
+        Some(Command::Batch {
+            inputs,
+            output_dir,
+            format,
+            overrides,
+        }) => run_batch(&inputs, output_dir.as_deref(), format, &overrides),
+        Some(Command::Serve { options }) => serve::serve(&options),
+    }
+}
+
+fn run_solve(io: &InputOutput, overrides: &Overrides, stream: bool) -> Result<()> {
+    let mut world_input: input::World = read_json(io.input.as_deref())?;
+    overrides.apply(&mut world_input);
     let world = World::try_from_json(world_input).context("failed to initialize world")?;
     log::debug!("world = {:?}", world);
 
-    let built = build::build(&world);
+    let mut output = open_output(io.output.as_deref())?;
+    solve_to(&world, stream, &mut output, io.format)
+}
+
+/// Solve the world and write the answer, preceded by the progress events in streaming mode
+fn solve_to(
+    world: &World,
+    stream: bool,
+    output: &mut (impl Write + Send),
+    format: Format,
+) -> Result<()> {
+    if !stream {
+        let answer = solve(world, None)?;
+        return write_json(output, &answer, format);
+    }
+
+    let mut stream_error = Ok(());
+    let answer = solve(
+        world,
+        Some(&mut |progress| {
+            if stream_error.is_ok() {
+                stream_error = write_json(output, &output::Event::Progress(progress), Format::Json);
+            }
+        }),
+    )?;
+    stream_error?;
+    write_json(output, &output::Event::Answer(answer), Format::Json)
+}
+
+fn run_validate(io: &InputOutput) -> Result<()> {
+    let world_input: input::World = read_json(io.input.as_deref())?;
+    let validation = validate(&world_input);
+    write_json(
+        &mut open_output(io.output.as_deref())?,
+        &validation,
+        io.format,
+    )?;
+
+    if !validation.valid {
+        bail!("The world is not valid");
+    }
+    Ok(())
+}
+
+fn run_evaluate(io: &InputOutput, itinerary: &std::path::Path) -> Result<()> {
+    let world_input: input::World = read_json(io.input.as_deref())?;
+    let world = World::try_from_json(world_input).context("failed to initialize world")?;
+    let itinerary: input::Itinerary = read_json(Some(itinerary))?;
 
-    // Print answers
-    let answer = built
-        .into_sorted_paths()
-        .filter(|item| !item.path.stops.is_empty())
-        .map(|item| output::Path::new(&world, &item))
-        .take(world.max_results)
-        .collect_vec();
-    let answer_str = serde_json::to_string(&answer).context("failed to encode answer")?;
-    println!("{}", answer_str);
+    let evaluation = evaluate(&world, &itinerary)?;
+    write_json(
+        &mut open_output(io.output.as_deref())?,
+        &evaluation,
+        io.format,
+    )?;
 
+    if evaluation.violation.is_some() {
+        bail!("The itinerary is not feasible");
+    }
     Ok(())
 }
+
+fn run_batch(
+    inputs: &[std::path::PathBuf],
+    output_dir: Option<&std::path::Path>,
+    format: Format,
+    overrides: &Overrides,
+) -> Result<()> {
+    // Check that no answer would overwrite another before solving any world
+    if output_dir.is_some() {
+        let mut inputs_by_name = std::collections::BTreeMap::new();
+        for input_path in inputs {
+            let file_name = input_path
+                .file_name()
+                .with_context(|| format!("{} is not a file", input_path.display()))?;
+            if let Some(other) = inputs_by_name.insert(file_name, input_path) {
+                bail!(
+                    "{} and {} would both be written to {}",
+                    other.display(),
+                    input_path.display(),
+                    file_name.to_string_lossy()
+                );
+            }
+        }
+    }
+
+    let mut failed = 0;
+    let mut stdout = open_output(None)?;
+
+    for input_path in inputs {
+        let answer = (|| -> Result<output::Answer> {
+            let mut world_input: input::World = read_json(Some(input_path))?;
+            overrides.apply(&mut world_input);
+            let world = World::try_from_json(world_input).context("failed to initialize world")?;
+            solve(&world, None)
+        })();
+
+        match output_dir {
+            Some(output_dir) => {
+                let file_name = input_path
+                    .file_name()
+                    .with_context(|| format!("{} is not a file", input_path.display()))?;
+                match answer {
+                    Ok(answer) => {
+                        let mut output = open_output(Some(&output_dir.join(file_name)))?;
+                        write_json(&mut output, &answer, format)?;
+                    }
+                    Err(error) => {
+                        failed += 1;
+                        log::error!("Failed to solve {}: {:#}", input_path.display(), error);
+                    }
+                }
+            }
+            None => {
+                failed += answer.is_err() as usize;
+                let (answer, error) = match answer {
+                    Ok(answer) => (Some(answer), None),
+                    Err(error) => (None, Some(format!("{:#}", error))),
+                };
+                let item = output::BatchItem {
+                    input: input_path.display().to_string(),
+                    answer,
+                    error,
+                };
+                write_json(&mut stdout, &item, Format::Json)?;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} out of {} worlds failed", failed, inputs.len());
+    }
+    Ok(())
+}
+
+/// Read from the file or, if none is given, from stdin
+fn read_json<T: DeserializeOwned>(path: Option<&std::path::Path>) -> Result<T> {
+    let mut json = String::new();
+    match path {
+        None => {
+            std::io::stdin()
+                .read_to_string(&mut json)
+                .context("failed to read stdin")?;
+            serde_json::from_str(&json).context("failed to parse stdin")
+        }
+        Some(path) => {
+            File::open(path)
+                .and_then(|mut file| file.read_to_string(&mut json))
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_str(&json)
+                .with_context(|| format!("failed to parse {}", path.display()))
+        }
+    }
+}
+
+/// Write to the file or, if none is given, to stdout
+fn open_output(path: Option<&std::path::Path>) -> Result<Box<dyn Write + Send>> {
+    Ok(match path {
+        None => Box::new(std::io::stdout()),
+        Some(path) => {
+            Box::new(BufWriter::new(File::create(path).with_context(|| {
+                format!("failed to create {}", path.display())
+            })?))
+        }
+    })
+}
+
+/// Write the value as a single line, or indented, and flush it, so that streamed events are seen
+/// right away
+fn write_json(output: &mut impl Write, value: &impl Serialize, format: Format) -> Result<()> {
+    match format {
+        Format::Json => serde_json::to_writer(&mut *output, value),
+        Format::Pretty => serde_json::to_writer_pretty(&mut *output, value),
+    }
+    .context("failed to encode answer")?;
+    writeln!(output).context("failed to write answer")?;
+    output.flush().context("failed to write answer")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(solver: &str) -> World {
+        let input: input::World = serde_json::from_value(serde_json::json!({
+            "sites": [
+                {"name": "a", "rideDurations": {"b": "10m"}, "duties": [], "serviceTime": "5m",
+                 "canStartHere": true, "visit": "ALWAYS"},
+                {"name": "b", "rideDurations": {"a": "10m"}, "duties": [], "serviceTime": "5m",
+                 "canStartHere": true, "visit": "ALWAYS"},
+            ],
+            "minStartAt": "09:00",
+            "maxEndAt": null,
+            "maxTestedExtensions": 2,
+            "maxBagItems": 10,
+            "maxResults": 10,
+            "maxImprovementSteps": 10,
+            "solver": solver,
+        }))
+        .unwrap();
+        World::try_from_json(input).unwrap()
+    }
+
+    /// Each event is a line of JSON, and the answer comes last
+    #[test]
+    fn stream_format() {
+        for (solver, phases) in [
+            ("HEURISTIC", vec!["BUILD", "IMPROVE"]),
+            ("EXACT", vec!["EXACT"]),
+        ] {
+            let mut output = vec![];
+            solve_to(&world(solver), true, &mut output, Format::Pretty).unwrap();
+            let events: Vec<serde_json::Value> = String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+
+            let (answer, progress) = events.split_last().unwrap();
+            assert_eq!(answer["type"], "ANSWER");
+            assert!(!answer["paths"].as_array().unwrap().is_empty());
+            let mut seen_phases: Vec<_> = progress
+                .iter()
+                .map(|event| {
+                    assert_eq!(event["type"], "PROGRESS");
+                    event["phase"].as_str().unwrap()
+                })
+                .collect();
+            seen_phases.dedup();
+            assert_eq!(seen_phases, phases);
+        }
+    }
+}
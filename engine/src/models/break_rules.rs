@@ -0,0 +1,14 @@
+use crate::models::*;
+use serde::{Deserialize, Serialize};
+
+/// Limits on how long a traveler can work, where riding and servicing count as work
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakRules {
+    /// The minimum duration of a break. Any waiting at least this long also counts as a break
+    pub min_break: Duration,
+    /// The maximum work time between two breaks
+    pub max_work_without_break: Duration,
+    /// The maximum work time for the whole route
+    pub max_total_work: Option<Duration>,
+}
@@ -0,0 +1,22 @@
+use crate::models::*;
+
+/// A position on Earth, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+impl Coordinates {
+    /// The great-circle distance, by the haversine formula
+    pub fn distance_to(self, other: Coordinates) -> Distance {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        let meters = 2.0 * EARTH_RADIUS_M * a.sqrt().asin();
+        Distance::from_m(meters.round() as i32)
+    }
+}
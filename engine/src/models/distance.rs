@@ -0,0 +1,94 @@
+use crate::parsers::parse_distance;
+use anyhow::{ensure, Error, Result};
+use nom::Finish;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+
+/// A distance in meters. Single rides fit in 32 bits, but their sums are stored in 64 bits so
+/// that long paths can not overflow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Distance(i64);
+
+impl Distance {
+    pub const ZERO: Distance = Distance(0);
+
+    pub fn from_m(m: i32) -> Self {
+        Distance(i64::from(m))
+    }
+
+    pub fn as_m(self) -> i64 {
+        self.0
+    }
+}
+
+impl Add for Distance {
+    type Output = Distance;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Distance(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Distance {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.as_m() == 0 {
+            write!(f, "0m")
+        } else {
+            let m = self.as_m() % 1000;
+            let km = self.as_m() / 1000;
+            if km != 0 {
+                write!(f, "{}km", km)?;
+            }
+            if m != 0 {
+                write!(f, "{}m", m)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl FromStr for Distance {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (input, distance) = parse_distance(s)
+            .finish()
+            .map_err(|e| Error::msg(e.to_string()))?;
+        ensure!(input.is_empty());
+        Ok(distance)
+    }
+}
+
+impl Serialize for Distance {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Distance {
+    fn deserialize<D>(deserializer: D) -> Result<Distance, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let as_string = String::deserialize(deserializer)?;
+        as_string.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+impl From<i32> for Distance {
+    fn from(m: i32) -> Self {
+        Distance::from_m(m)
+    }
+}
@@ -0,0 +1,35 @@
+use crate::models::*;
+
+/// A dense square matrix with the ride distance between each pair of sites
+#[derive(Debug, Clone)]
+pub struct DistanceMatrix {
+    side: usize,
+    entries: Vec<i32>,
+}
+
+const MISSING: i32 = -1;
+
+impl DistanceMatrix {
+    /// Create a matrix with the diagonal as zero and the other entries as `None`
+    pub fn new(side: usize) -> Self {
+        let mut entries = vec![MISSING; side * side];
+        for i in 0..side {
+            entries[i * side + i] = 0;
+        }
+        DistanceMatrix { side, entries }
+    }
+
+    pub fn get(&self, from: SiteId, to: SiteId) -> Option<Distance> {
+        match self.entries[from.as_usize() * self.side + to.as_usize()] {
+            MISSING => None,
+            m => Some(Distance::from_m(m)),
+        }
+    }
+
+    /// Panics if `value` is negative or does not fit in 32 bits, like a single ride always does
+    pub fn set(&mut self, from: SiteId, to: SiteId, value: Distance) {
+        assert!(value >= Distance::ZERO, "distances cannot be negative");
+        self.entries[from.as_usize() * self.side + to.as_usize()] =
+            i32::try_from(value.as_m()).expect("a single ride fits in 32 bits");
+    }
+}
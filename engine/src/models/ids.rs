@@ -1,19 +1,23 @@
 use anyhow::{ensure, Context, Result};
 use std::collections::BTreeMap;
+use std::error::Error;
 use std::fmt;
 
 macro_rules! derive_id {
-    ($name:ident) => {
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-        pub struct $name(u8);
+    ($name:ident, $repr:ty) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name($repr);
 
         impl InternalId for $name {
+            const NAME: &'static str = stringify!($name);
+            const MAX_COUNT: usize = <$repr>::MAX as usize + 1;
+
             fn as_usize(self) -> usize {
                 self.0 as usize
             }
 
             fn from_usize(i: usize) -> Self {
-                $name(u8::try_from(i).unwrap())
+                $name(<$repr>::try_from(i).unwrap())
             }
         }
 
@@ -32,23 +36,60 @@ macro_rules! derive_id {
     };
 }
 
-derive_id! {SiteId}
+derive_id! {SiteId, u16}
+derive_id! {TravelerId, u8}
 
 pub trait InternalId: Copy {
+    /// A human-readable name for the kind of entity
+    const NAME: &'static str;
+    /// The maximum number of distinct ids that can be represented
+    const MAX_COUNT: usize;
+
     fn as_usize(self) -> usize;
 
+    /// Panics if `i` is not less than `MAX_COUNT`
     fn from_usize(i: usize) -> Self;
 }
 
+/// Returned when more entities are declared than their id type can represent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyEntities {
+    pub entity: &'static str,
+    pub count: usize,
+    pub max_count: usize,
+}
+
+impl fmt::Display for TooManyEntities {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Got {} entities of type {}, but at most {} are supported",
+            self.count, self.entity, self.max_count
+        )
+    }
+}
+
+impl Error for TooManyEntities {}
+
 #[derive(Debug, Clone)]
 pub struct IdConverter<ID>(BTreeMap<String, ID>);
 
 impl<ID: InternalId> IdConverter<ID> {
     pub fn new(names: impl IntoIterator<Item = String>) -> Result<Self> {
+        let names: Vec<_> = names.into_iter().collect();
+        if names.len() > ID::MAX_COUNT {
+            return Err(TooManyEntities {
+                entity: ID::NAME,
+                count: names.len(),
+                max_count: ID::MAX_COUNT,
+            }
+            .into());
+        }
+
         let mut map = BTreeMap::new();
         for (i, name) in names.into_iter().enumerate() {
-            let previous = map.insert(name, ID::from_usize(i));
-            ensure!(previous.is_none());
+            let previous = map.insert(name.clone(), ID::from_usize(i));
+            ensure!(previous.is_none(), "Duplicate {} name {}", ID::NAME, name);
         }
         Ok(IdConverter(map))
     }
@@ -60,3 +101,25 @@ impl<ID: InternalId> IdConverter<ID> {
             .with_context(|| format!("Could not find entity with name {}", name))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn too_many_entities() {
+        let converter = IdConverter::<SiteId>::new((0..SiteId::MAX_COUNT).map(|i| i.to_string()));
+        assert!(converter.is_ok());
+
+        let error = IdConverter::<SiteId>::new((0..SiteId::MAX_COUNT + 1).map(|i| i.to_string()))
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TooManyEntities>(),
+            Some(&TooManyEntities {
+                entity: "SiteId",
+                count: SiteId::MAX_COUNT + 1,
+                max_count: SiteId::MAX_COUNT,
+            })
+        );
+    }
+}
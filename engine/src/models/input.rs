@@ -1,7 +1,11 @@
-use crate::models::*;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+pub use crate::models::{
+    BoundedTimeWindow, BreakRules, Criterion, Direction, Distance, Duration, Objective, PathEnd,
+    Ranking, RideEstimation, RideSample, Solver, SpeedBand, Timestamp, Visit,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct World {
@@ -11,6 +15,76 @@ pub struct World {
     pub max_tested_extensions: i32,
     pub max_bag_items: i32,
     pub max_results: i32,
+    /// How many times each finished path can be improved by local search. Defaults to 0, which
+    /// disables the improvement phase, so that the answers stay the same unless it is asked for
+    pub max_improvement_steps: Option<i32>,
+    /// Stop searching after this wall-clock time, returning the best paths so far
+    pub time_limit: Option<Duration>,
+    /// How many threads to search with. Defaults to the number of CPU cores
+    pub threads: Option<i32>,
+    /// The criteria used to compare paths. When empty, all criteria are used
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+    /// How the returned paths are ordered
+    #[serde(default)]
+    pub ranking: Ranking,
+    /// When present, missing rides between sites with coordinates are estimated
+    pub ride_estimation: Option<RideEstimation>,
+    /// When present, breaks are inserted into each route as needed
+    pub break_rules: Option<BreakRules>,
+    #[serde(default)]
+    pub solver: Solver,
+    /// Where the route of each traveler must end
+    #[serde(default)]
+    pub path_end: PathEnd,
+    /// When empty, a single traveler is assumed, starting from any site that `can_start_here`
+    #[serde(default)]
+    pub travelers: Vec<Traveler>,
+    /// When present, the plan continues from the current state of the day
+    pub replan: Option<Replan>,
+    /// Visits that must happen in order, in the same route
+    #[serde(default)]
+    pub precedences: Vec<Precedence>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Precedence {
+    pub before: String,
+    pub after: String,
+    /// Whether `before` must be the stop right before `after`
+    #[serde(default)]
+    pub immediately: bool,
+    /// The longest time from the end of the service at `before` to the start of the service at
+    /// `after`
+    pub within: Option<Duration>,
+}
+
+/// The state of the day when re-planning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replan {
+    /// Where the travelers are now. The others start as usual
+    #[serde(default)]
+    pub positions: Vec<Position>,
+    /// The sites already visited, which are not visited again
+    #[serde(default)]
+    pub completed_sites: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Position {
+    /// Can be omitted when there is a single traveler
+    pub traveler: Option<String>,
+    pub site: String,
+    pub at: Timestamp,
+    /// Where the route started. Can be omitted when the traveler has a single start site
+    pub started_in: Option<String>,
+    /// The work already done on the route, for the break rules. Defaults to none
+    pub total_work: Option<Duration>,
+    /// When the last break ended. Without it, all the work was done since the last break
+    pub last_break_end: Option<Timestamp>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,8 +92,70 @@ pub struct World {
 pub struct Site {
     pub name: String,
     pub ride_durations: BTreeMap<String, Duration>,
+    /// When no site has any distance, distances are not considered at all
+    #[serde(default)]
+    pub ride_distances: BTreeMap<String, Distance>,
+    /// Rides that depend on the departure time. They take precedence over `ride_durations`
+    #[serde(default)]
+    pub ride_profiles: BTreeMap<String, Vec<RideSample>>,
     pub duties: Vec<BoundedTimeWindow>,
+    /// Constrain visits without duty. When empty, the site is always open
+    #[serde(default)]
+    pub opening_hours: Vec<BoundedTimeWindow>,
     pub service_time: Duration,
     pub can_start_here: bool,
+    #[serde(default)]
+    pub can_end_here: bool,
     pub visit: Visit,
+    /// Collected when the site is visited, to favor some optional sites over others
+    #[serde(default)]
+    pub prize: i32,
+    /// In degrees. Used to estimate missing rides
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    /// In degrees. Used to estimate missing rides
+    #[serde(default)]
+    pub longitude: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Traveler {
+    pub name: String,
+    /// When empty, any site that `can_start_here` is used
+    #[serde(default)]
+    pub start_sites: Vec<String>,
+    /// When empty, any site that `can_end_here` is used
+    #[serde(default)]
+    pub end_sites: Vec<String>,
+    /// Defaults to the value in `World`
+    pub min_start_at: Option<Timestamp>,
+    /// Defaults to the value in `World`
+    pub max_end_at: Option<Timestamp>,
+}
+
+/// A plan made by hand, to be checked by the engine
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Itinerary {
+    /// In the order of the travelers
+    pub routes: Vec<ItineraryRoute>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItineraryRoute {
+    /// Can be omitted when there is a single traveler
+    pub traveler: Option<String>,
+    pub start_in: String,
+    /// The return to the end site, if needed, is added automatically
+    pub stops: Vec<ItineraryStop>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItineraryStop {
+    pub site: String,
+    /// Must be one of the duties of the site
+    pub duty: Option<BoundedTimeWindow>,
 }
@@ -1,27 +1,47 @@
+mod break_rules;
 mod capped;
+mod coordinates;
+mod distance;
+mod distance_matrix;
 mod duration;
 mod ids;
 pub mod input;
+mod objectives;
 pub mod output;
 mod path;
 mod path_cost;
-mod point;
+mod precedence;
+mod ride_estimation;
 mod ride_matrix;
+mod ride_profile;
 mod site;
+mod site_set;
+mod solver;
 mod stop;
 mod time_window;
 mod timestamp;
+mod traveler;
 mod world;
 
+pub use break_rules::*;
 pub use capped::*;
+pub use coordinates::*;
+pub use distance::*;
+pub use distance_matrix::*;
 pub use duration::*;
 pub use ids::*;
+pub use objectives::*;
 pub use path::*;
 pub use path_cost::*;
-pub use point::*;
+pub use precedence::*;
+pub use ride_estimation::*;
 pub use ride_matrix::*;
+pub use ride_profile::*;
 pub use site::*;
+pub use site_set::*;
+pub use solver::*;
 pub use stop::*;
 pub use time_window::*;
 pub use timestamp::*;
+pub use traveler::*;
 pub use world::*;
@@ -0,0 +1,269 @@
+use crate::models::*;
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// One of the fields of `PathCost` that can be optimized
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Criterion {
+    TotalRide,
+    TotalTime,
+    TotalDistance,
+    StopsOnDuty,
+    Stops,
+    TotalPrize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Direction {
+    Minimize,
+    Maximize,
+}
+
+/// How the final paths are ordered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Ranking {
+    /// Paths dominated by fewer others come first, then the ones that dominate more others
+    #[default]
+    Dominance,
+    /// Paths with the smallest weighted sum of the (minimized) criteria come first
+    Weighted,
+    /// Paths are compared by each criterion in turn, in the order they were declared
+    Lexicographic,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Objective {
+    pub criterion: Criterion,
+    /// Defaults to the natural direction of the criterion
+    pub direction: Option<Direction>,
+    /// Only used by `Ranking::Weighted`. Defaults to 1
+    pub weight: Option<f64>,
+}
+
+/// The active criteria, used both for dominance and for ranking the final paths
+#[derive(Debug, Clone)]
+pub struct Objectives {
+    objectives: Vec<Objective>,
+    ranking: Ranking,
+}
+
+impl Criterion {
+    pub const ALL: [Criterion; 6] = [
+        Criterion::TotalRide,
+        Criterion::TotalTime,
+        Criterion::TotalDistance,
+        Criterion::StopsOnDuty,
+        Criterion::Stops,
+        Criterion::TotalPrize,
+    ];
+
+    pub fn natural_direction(self) -> Direction {
+        match self {
+            Criterion::TotalRide | Criterion::TotalTime | Criterion::TotalDistance => {
+                Direction::Minimize
+            }
+            Criterion::StopsOnDuty | Criterion::Stops | Criterion::TotalPrize => {
+                Direction::Maximize
+            }
+        }
+    }
+
+    /// The raw value of this criterion, in seconds, meters or units
+    pub fn value(self, cost: &PathCost) -> i64 {
+        match self {
+            Criterion::TotalRide => i64::from(cost.total_ride.as_s()),
+            Criterion::TotalTime => i64::from(cost.total_time.as_s()),
+            Criterion::TotalDistance => cost.total_distance.as_m(),
+            Criterion::StopsOnDuty => i64::from(cost.stops_on_duty.0),
+            Criterion::Stops => i64::from(cost.stops.0),
+            Criterion::TotalPrize => i64::from(cost.total_prize.0),
+        }
+    }
+}
+
+impl Objective {
+    /// The value of the criterion, oriented so that lesser is better
+    fn value(&self, cost: &PathCost) -> i64 {
+        let value = self.criterion.value(cost);
+        match self
+            .direction
+            .unwrap_or_else(|| self.criterion.natural_direction())
+        {
+            Direction::Minimize => value,
+            Direction::Maximize => -value,
+        }
+    }
+}
+
+impl Objectives {
+    /// When `objectives` is empty, all criteria are used in their natural direction
+    pub fn try_new(objectives: Vec<Objective>, ranking: Ranking) -> Result<Self> {
+        for objective in &objectives {
+            if let Some(weight) = objective.weight {
+                ensure!(
+                    weight.is_finite() && weight >= 0.0,
+                    "Invalid weight {} for {:?}",
+                    weight,
+                    objective.criterion
+                );
+            }
+        }
+
+        let objectives = if objectives.is_empty() {
+            Criterion::ALL
+                .iter()
+                .map(|&criterion| Objective {
+                    criterion,
+                    direction: None,
+                    weight: None,
+                })
+                .collect()
+        } else {
+            objectives
+        };
+        Ok(Objectives {
+            objectives,
+            ranking,
+        })
+    }
+
+    /// Return `Less` if `a` is better than `b` for some criteria and not worse for any other
+    pub fn compare(&self, a: &PathCost, b: &PathCost) -> Option<Ordering> {
+        let mut result = Ordering::Equal;
+        for objective in &self.objectives {
+            match (result, objective.value(a).cmp(&objective.value(b))) {
+                (_, Ordering::Equal) => {}
+                (Ordering::Equal, ordering) => result = ordering,
+                (current, ordering) if current != ordering => return None,
+                _ => {}
+            }
+        }
+        Some(result)
+    }
+
+    /// The values of the criteria, oriented so that lesser is better: a cost dominates another
+    /// when none of its values is greater
+    pub fn values(&self, cost: &PathCost) -> Vec<i64> {
+        self.objectives
+            .iter()
+            .map(|objective| objective.value(cost))
+            .collect()
+    }
+
+    /// Order two costs according to the preferred ranking, where `Less` comes first. Return
+    /// `None` when the ranking does not tell them apart
+    pub fn rank(&self, a: &PathCost, b: &PathCost) -> Option<Ordering> {
+        let ordering = match self.ranking {
+            Ranking::Dominance => return None,
+            Ranking::Weighted => self.weighted_sum(a).total_cmp(&self.weighted_sum(b)),
+            Ranking::Lexicographic => self
+                .objectives
+                .iter()
+                .map(|objective| objective.value(a).cmp(&objective.value(b)))
+                .find(|&ordering| ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal),
+        };
+        Some(ordering)
+    }
+
+    fn weighted_sum(&self, cost: &PathCost) -> f64 {
+        self.objectives
+            .iter()
+            .map(|objective| objective.weight.unwrap_or(1.0) * objective.value(cost) as f64)
+            .sum()
+    }
+}
+
+impl Default for Objectives {
+    fn default() -> Self {
+        Objectives::try_new(vec![], Ranking::Dominance).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cost(total_ride: i32, stops: i32) -> PathCost {
+        let mut cost = PathCost::mock();
+        cost.total_ride = Duration::from(total_ride);
+        cost.stops = std::cmp::Reverse(stops);
+        cost
+    }
+
+    #[test]
+    fn compare() {
+        let objectives = Objectives::default();
+        assert_eq!(
+            objectives.compare(&cost(1, 2), &cost(2, 2)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            objectives.compare(&cost(1, 2), &cost(1, 1)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(objectives.compare(&cost(1, 1), &cost(2, 2)), None);
+        assert_eq!(
+            objectives.compare(&cost(1, 1), &cost(1, 1)),
+            Some(Ordering::Equal)
+        );
+
+        // Only fewer rides matter
+        let objectives = Objectives::try_new(
+            vec![Objective {
+                criterion: Criterion::TotalRide,
+                direction: None,
+                weight: None,
+            }],
+            Ranking::Dominance,
+        )
+        .unwrap();
+        assert_eq!(
+            objectives.compare(&cost(1, 1), &cost(2, 2)),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn rank() {
+        let objectives = Objectives::try_new(
+            vec![
+                Objective {
+                    criterion: Criterion::Stops,
+                    direction: None,
+                    weight: Some(60.0),
+                },
+                Objective {
+                    criterion: Criterion::TotalRide,
+                    direction: None,
+                    weight: None,
+                },
+            ],
+            Ranking::Weighted,
+        )
+        .unwrap();
+        // Each stop is worth one minute of ride
+        assert_eq!(
+            objectives.rank(&cost(100, 2), &cost(30, 1)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            objectives.rank(&cost(100, 2), &cost(50, 1)),
+            Some(Ordering::Less)
+        );
+
+        let objectives = Objectives {
+            ranking: Ranking::Lexicographic,
+            ..objectives
+        };
+        assert_eq!(
+            objectives.rank(&cost(100, 2), &cost(30, 1)),
+            Some(Ordering::Less)
+        );
+    }
+}
@@ -1,16 +1,222 @@
+use crate::build;
 use crate::models;
 use crate::models::*;
-use crate::path_bag::PathBagItem;
+use crate::path_bag::PathBag;
 use serde::{Deserialize, Serialize};
 
+pub use crate::models::{BoundedTimeWindow, Distance, Duration, PathCost, StopKind, Timestamp};
+
+/// Written as one line of JSON each, in streaming mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Event {
+    /// After each iteration of a phase of the search
+    Progress(Progress),
+    /// Always the last event
+    Answer(Answer),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Progress {
+    pub phase: Phase,
+    pub iteration: usize,
+    /// How many paths are left for the next iteration: the partial paths to extend, or the
+    /// finished paths to improve
+    pub bag_size: usize,
+    /// The number of partial paths for each dominance score. Empty outside of the build phase
+    pub count_by_score: Vec<ScoreCount>,
+    /// How many extended paths were discarded by state dominance in this iteration
+    pub pruned: usize,
+    /// The best finished paths so far
+    pub best_paths: Vec<Path>,
+}
+
+/// The phase of the search that reports its progress
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Phase {
+    /// Extend a bag of partial paths, with the heuristic solver
+    Build,
+    /// Extend every partial path, with the exact solver
+    Exact,
+    /// Rearrange the stops of the finished paths
+    Improve,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreCount {
+    pub dominated_by: i32,
+    pub dominates: i32,
+    pub count: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Answer {
+    pub paths: Vec<Path>,
+    /// Whether the search was stopped by the time limit, so better paths may exist
+    pub truncated: bool,
+    /// Why no path was found, only present when `paths` is empty
+    pub diagnosis: Option<Diagnosis>,
+}
+
+/// Explains why no path visits all the must-visit sites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnosis {
+    /// The must-visit sites that no explored path visited
+    pub unreached_sites: Vec<UnreachedSite>,
+    /// The pairs of must-visit sites whose opening hours or duties do not allow a traveler to
+    /// service one after the other, in any order
+    pub window_conflicts: Vec<WindowConflict>,
+    /// The missing rides that would connect the sites that no traveler can ride to
+    pub missing_rides: Vec<MissingRide>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnreachedSite {
+    pub site: String,
+    pub reason: UnreachedReason,
+    /// The earliest time any traveler could get there, ignoring the services on the way
+    pub earliest_arrival: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum UnreachedReason {
+    /// No sequence of rides leads there from a start site
+    NoRide,
+    /// No opening window or duty starts after the earliest arrival
+    Closed,
+    /// The service can not end before the traveler's `maxEndAt`
+    TooLate,
+    /// The site can be serviced on its own, but not together with the other sites, or the search
+    /// dropped the paths that could
+    NotExplored,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowConflict {
+    pub first: String,
+    /// The conflicting duty of the first site, or none for its opening hours
+    pub first_duty: Option<BoundedTimeWindow>,
+    pub second: String,
+    pub second_duty: Option<BoundedTimeWindow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingRide {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Validation {
+    pub valid: bool,
+    pub issues: Vec<Issue>,
+}
+
+/// A problem found in the input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Issue {
+    /// Where the problem is, like `$.sites[2].duties[0]`
+    pub path: String,
+    pub message: String,
+}
+
+/// The schedule of a given itinerary, or the first reason why it is not feasible
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    pub path: Option<Path>,
+    pub violation: Option<Violation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Violation {
+    /// There is no ride between two consecutive stops
+    MissingRide {
+        traveler: String,
+        from: String,
+        to: String,
+    },
+    /// The site has no opening window left when the traveler is ready for the service
+    #[serde(rename_all = "camelCase")]
+    Closed {
+        traveler: String,
+        site: String,
+        ready_at: Timestamp,
+    },
+    /// The service ends after its duty or opening window
+    #[serde(rename_all = "camelCase")]
+    WindowMissed {
+        traveler: String,
+        site: String,
+        window: BoundedTimeWindow,
+        service_end: Timestamp,
+    },
+    /// The break rules do not allow that much work
+    TooMuchWork { traveler: String, site: String },
+    /// The visit to `after` does not follow the visit to `before` as required
+    PrecedenceBroken {
+        traveler: String,
+        before: String,
+        after: String,
+    },
+    /// The route ends after the traveler's `maxEndAt`
+    #[serde(rename_all = "camelCase")]
+    LateEnd {
+        traveler: String,
+        end_at: Timestamp,
+        max_end_at: Timestamp,
+    },
+    /// Some sites that must be visited are not
+    Unvisited { sites: Vec<String> },
+}
+
+/// The result of solving one of the worlds of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItem {
+    pub input: String,
+    pub answer: Option<Answer>,
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Path {
+    /// The routes of each traveler that has at least one stop
+    pub routes: Vec<Route>,
+    pub cost: PathCost,
+    pub is_dominated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Route {
+    pub traveler: String,
     pub start_in: String,
     pub start_at: Timestamp,
     pub stops: Vec<Stop>,
+    pub breaks: Vec<Break>,
     pub cost: PathCost,
-    pub is_dominated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Break {
+    pub site: String,
+    pub start: Timestamp,
+    pub end: Timestamp,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +224,7 @@ pub struct Path {
 pub struct Stop {
     pub site: String,
     pub duty: Option<BoundedTimeWindow>,
+    pub kind: StopKind,
     pub ride_start: Timestamp,
     pub ride_end: Timestamp,
     pub service_start: Timestamp,
@@ -25,30 +232,120 @@ pub struct Stop {
     pub ride: Duration,
     pub wait: Duration,
     pub service: Duration,
+    pub distance: Option<Distance>,
+    /// Whether the ride was estimated from the coordinates of the sites
+    pub estimated: bool,
+    pub prize: i32,
+}
+
+impl Answer {
+    pub(crate) fn new(world: &World, paths: &PathBag, truncated: bool) -> Self {
+        Answer {
+            paths: best_paths(world, paths),
+            truncated,
+            diagnosis: None,
+        }
+    }
+}
+
+impl Progress {
+    pub(crate) fn new(world: &World, progress: &build::Progress) -> Self {
+        Progress {
+            phase: progress.phase,
+            iteration: progress.iteration,
+            bag_size: progress.pending,
+            count_by_score: progress
+                .base_paths
+                .map(|base_paths| base_paths.count_by_score())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|((dominated_by, dominates), count)| ScoreCount {
+                    dominated_by,
+                    dominates,
+                    count,
+                })
+                .collect(),
+            pruned: progress.pruned,
+            best_paths: best_paths(world, progress.finished_paths),
+        }
+    }
+}
+
+/// The first `max_results` non-empty paths, in the preferred order
+fn best_paths(world: &World, paths: &PathBag) -> Vec<Path> {
+    paths
+        .sorted_paths()
+        .into_iter()
+        .filter(|(path, _)| !path.visited_sites.is_empty())
+        .map(|(path, is_dominated)| Path::new(world, path, is_dominated))
+        .take(world.max_results)
+        .collect()
 }
 
 impl Path {
-    pub fn new(world: &World, item: &PathBagItem) -> Self {
-        let path = &item.path;
+    pub(crate) fn new(world: &World, path: &models::Path, is_dominated: bool) -> Self {
+        let mut routes: Vec<_> = path
+            .routes()
+            .filter(|route| !route.stops.is_empty())
+            .map(|route| Route::new(world, route))
+            .collect();
+        routes.reverse();
         Path {
-            start_in: world[path.start_in].name.clone(),
-            start_at: path.start_at,
-            stops: path
+            routes,
+            cost: path.cost,
+            is_dominated,
+        }
+    }
+}
+
+impl Route {
+    pub(crate) fn new(world: &World, route: &models::Path) -> Self {
+        let mut breaks = vec![];
+        let mut prev_site = route.start_in;
+        for stop in &route.stops {
+            if let Some(duration) = stop.break_before_ride {
+                breaks.push(Break {
+                    site: world[prev_site].name.clone(),
+                    start: stop.ride_start - duration,
+                    end: stop.ride_start,
+                });
+            }
+            if stop.break_before_service {
+                breaks.push(Break {
+                    site: world[stop.site].name.clone(),
+                    start: stop.ride_end,
+                    end: stop.service_start,
+                });
+            }
+            prev_site = stop.site;
+        }
+
+        let mut prev_site = route.start_in;
+        Route {
+            traveler: world[route.traveler].name.clone(),
+            start_in: world[route.start_in].name.clone(),
+            start_at: route.start_at,
+            stops: route
                 .stops
                 .iter()
-                .map(|stop| Stop::new(world, stop))
+                .map(|stop| {
+                    let stop_output = Stop::new(world, prev_site, stop);
+                    prev_site = stop.site;
+                    stop_output
+                })
                 .collect(),
-            cost: path.cost,
-            is_dominated: item.dominated_by > 0,
+            breaks,
+            cost: route.route_cost,
         }
     }
 }
 
 impl Stop {
-    pub fn new(world: &World, stop: &models::Stop) -> Self {
+    pub(crate) fn new(world: &World, prev_site: SiteId, stop: &models::Stop) -> Self {
         Stop {
             site: world[stop.site].name.clone(),
             duty: stop.duty,
+            kind: stop.kind,
             ride_start: stop.ride_start,
             ride_end: stop.ride_end,
             service_start: stop.service_start,
@@ -56,6 +353,9 @@ impl Stop {
             ride: stop.ride_end - stop.ride_start,
             wait: stop.service_start - stop.ride_end,
             service: stop.service_end - stop.service_start,
+            distance: stop.ride_distance,
+            estimated: world.is_ride_estimated(prev_site, stop.site),
+            prize: stop.prize,
         }
     }
 }
@@ -1,61 +1,189 @@
 use crate::models::*;
-use std::collections::BTreeSet;
 use std::fmt;
+use std::sync::Arc;
 
+/// The route followed by one traveler, chained after the routes of the previous travelers
 #[derive(Debug, Clone)]
 pub struct Path {
+    pub traveler: TravelerId,
     pub start_in: SiteId,
     pub start_at: Timestamp,
     pub stops: Vec<Stop>,
+    /// The cost of the whole path, including the routes of the previous travelers
     pub cost: PathCost,
-    pub visited_sites: BTreeSet<SiteId>,
+    /// The cost of this route alone
+    pub route_cost: PathCost,
+    /// The sites visited by the whole path, including the routes of the previous travelers
+    pub visited_sites: SiteSet,
+    pub previous: Option<Arc<Path>>,
+    /// How much this route could still be delayed without breaking any window
+    pub slack: CappedMax<Duration>,
+    /// Work since the last break, only tracked with break rules
+    pub work_since_break: Duration,
+    /// Work on this route, only tracked with break rules
+    pub total_work: Duration,
+}
+
+/// Why a route can not be scheduled, at its first failing stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// There is no ride from the previous site
+    MissingRide { stop: usize },
+    /// The site has no opening window left when the traveler is ready for the service
+    Closed { stop: usize, ready_at: Timestamp },
+    /// The service ends after its duty or opening window
+    WindowMissed {
+        stop: usize,
+        window: BoundedTimeWindow,
+        service_end: Timestamp,
+    },
+    /// The break rules do not allow that much work
+    TooMuchWork { stop: usize },
+    /// The visit does not follow the visit to `before` as required
+    PrecedenceBroken { stop: usize, before: SiteId },
 }
 
 impl Path {
-    pub fn empty(start_in: SiteId, start_at: Timestamp) -> Self {
+    pub fn empty(traveler: &Traveler, start_in: SiteId) -> Self {
+        let cost = PathCost::new(traveler.departure_at(), &[]);
+        let (work_since_break, total_work) = traveler.departure_work();
         Path {
+            traveler: traveler.id,
             start_in,
-            start_at,
+            start_at: traveler.departure_at(),
             stops: vec![],
-            cost: PathCost::new(start_at, &[]),
-            visited_sites: BTreeSet::new(),
+            cost,
+            route_cost: cost,
+            visited_sites: SiteSet::new(),
+            previous: None,
+            slack: CappedMax::Max,
+            work_since_break,
+            total_work,
         }
     }
 
-    pub fn try_schedule(world: &World, start_in: SiteId, stops: &[StopSketch]) -> Option<Self> {
+    /// Schedule a single route, not chained to any previous one
+    pub fn try_schedule(
+        world: &World,
+        traveler: TravelerId,
+        start_in: SiteId,
+        stops: &[StopSketch],
+    ) -> Option<Self> {
+        Self::schedule(world, traveler, start_in, stops).ok()
+    }
+
+    /// Like [`Path::try_schedule`], but explain why the route is not feasible
+    pub fn schedule(
+        world: &World,
+        traveler: TravelerId,
+        start_in: SiteId,
+        stops: &[StopSketch],
+    ) -> Result<Self, ScheduleError> {
         // Forward schedule and calculate compressions
         let mut prev_site = start_in;
-        let mut prev_end = world.min_start_at;
+        let mut prev_end = world[traveler].departure_at();
         let mut compressions = Vec::with_capacity(stops.len());
         let mut total_compression = Duration::ZERO;
         let mut slack = CappedMax::Max;
+        // The slack if the waits end up not being compressed
+        let mut uncompressed_slack = CappedMax::Max;
         let mut path_stops = Vec::with_capacity(stops.len());
+        // Work since the last break and on the whole route
+        let (mut work, mut total_work) = world[traveler].departure_work();
 
-        for stop in stops {
+        for (i, stop) in stops.iter().enumerate() {
             let site = &world[stop.site];
-            let ride = world.ride_matrix.get(prev_site, stop.site)?;
-            let ride_start = prev_end;
-            let ride_end = ride_start + ride;
-            let service_start = match stop.duty {
-                Some(duty) if duty.start() > ride_end => duty.start(),
-                _ => ride_end,
+            let missing_ride = ScheduleError::MissingRide { stop: i };
+            let service_time = match stop.kind {
+                StopKind::Visit => site.service_time,
+                StopKind::Return => Duration::ZERO,
             };
+
+            let mut ride_start = prev_end;
+            let mut ride = world
+                .ride(prev_site, stop.site, ride_start)
+                .ok_or(missing_ride)?;
+            let mut break_before_ride = None;
+            if let Some(rules) = &world.break_rules {
+                if work + ride > rules.max_work_without_break {
+                    break_before_ride = Some(rules.min_break);
+                    ride_start += rules.min_break;
+                    ride = world
+                        .ride(prev_site, stop.site, ride_start)
+                        .ok_or(missing_ride)?;
+                    work = Duration::ZERO;
+                }
+            }
+            let ride_end = ride_start + ride;
+            let ride_distance = world.distance(prev_site, stop.site);
+            work += ride;
+
+            let (mut service_window, mut service_start) =
+                Self::schedule_service(site, stop, ride_end).ok_or(ScheduleError::Closed {
+                    stop: i,
+                    ready_at: ride_end,
+                })?;
+            let mut break_before_service = false;
+            if let Some(rules) = &world.break_rules {
+                if service_start - ride_end >= rules.min_break {
+                    break_before_service = true;
+                } else if work + service_time > rules.max_work_without_break {
+                    // Wait long enough to take a break before the service
+                    break_before_service = true;
+                    let ready_at = ride_end + rules.min_break;
+                    (service_window, service_start) = Self::schedule_service(site, stop, ready_at)
+                        .ok_or(ScheduleError::Closed { stop: i, ready_at })?;
+                }
+                if break_before_service {
+                    work = Duration::ZERO;
+                }
+                work += service_time;
+                total_work += ride + service_time;
+                if work > rules.max_work_without_break
+                    || rules.max_total_work.is_some_and(|max| total_work > max)
+                {
+                    return Err(ScheduleError::TooMuchWork { stop: i });
+                }
+            }
+
+            if stop.kind == StopKind::Visit {
+                // The timing is only checked once the waits are compressed
+                if let Some(precedence) = world
+                    .precedences_to(stop.site)
+                    .find(|precedence| !precedence.is_ordered(&world.completed_sites, &path_stops))
+                {
+                    return Err(ScheduleError::PrecedenceBroken {
+                        stop: i,
+                        before: precedence.before,
+                    });
+                }
+            }
+
             let waiting = service_start - ride_end;
-            let compression = slack.min_with(waiting);
-            let service_end = service_start + site.service_time;
-            let stop_slack = match stop.duty {
-                Some(duty) => {
-                    if duty.end() >= service_end {
-                        duty.end() - service_end
+            let compressible = match &world.break_rules {
+                // Keep the break
+                Some(rules) if break_before_service => waiting - rules.min_break,
+                _ => waiting,
+            };
+            let compression = slack.min_with(compressible);
+            let service_end = service_start + service_time;
+            let stop_slack = match service_window {
+                Some(window) => {
+                    if window.end() >= service_end {
+                        window.end() - service_end
                     } else {
-                        // Unfeasible
-                        return None;
+                        return Err(ScheduleError::WindowMissed {
+                            stop: i,
+                            window,
+                            service_end,
+                        });
                     }
                 }
                 _ => Duration::ZERO,
             };
             slack -= compression;
             slack = slack.min(CappedMax::Value(stop_slack));
+            uncompressed_slack = uncompressed_slack.min(CappedMax::Value(stop_slack));
 
             prev_site = site.id;
             prev_end = service_end;
@@ -64,14 +192,27 @@ impl Path {
             path_stops.push(Stop {
                 site: stop.site,
                 duty: stop.duty,
+                kind: stop.kind,
                 ride_start,
                 ride_end,
                 service_start,
                 service_end,
+                ride_distance,
+                prize: match stop.kind {
+                    StopKind::Visit => site.prize,
+                    StopKind::Return => 0,
+                },
+                break_before_ride,
+                break_before_service,
             });
         }
 
-        // Apply compressions
+        // Apply compressions. Delaying a time-dependent ride may change its duration, so in that
+        // case the compressed schedule is checked again and discarded if no longer feasible
+        let uncompressed_stops = world
+            .ride_matrix
+            .is_time_dependent()
+            .then(|| path_stops.clone());
         for (path_stop, compression) in path_stops.iter_mut().zip(compressions) {
             path_stop.ride_start += total_compression;
             path_stop.ride_end += total_compression;
@@ -79,17 +220,139 @@ impl Path {
             path_stop.service_start += total_compression;
             path_stop.service_end += total_compression;
         }
+        if let Some(uncompressed_stops) = uncompressed_stops {
+            if !Self::retime_rides(world, traveler, start_in, &mut path_stops) {
+                path_stops = uncompressed_stops;
+                slack = uncompressed_slack;
+            }
+        }
+
+        // Compressing the waits brings the services closer together, so the timed precedences are
+        // checked on the final schedule
+        for (i, stop) in path_stops.iter().enumerate() {
+            if stop.kind != StopKind::Visit {
+                continue;
+            }
+            if let Some(precedence) = world.precedences_to(stop.site).find(|precedence| {
+                !precedence.is_met(&world.completed_sites, &path_stops[..i], stop.service_start)
+            }) {
+                return Err(ScheduleError::PrecedenceBroken {
+                    stop: i,
+                    before: precedence.before,
+                });
+            }
+        }
 
         let start_at = path_stops.first().map_or(prev_end, |stop| stop.ride_start);
-        Some(Path {
+        let cost = PathCost::new(start_at, &path_stops);
+        Ok(Path {
+            traveler,
             start_in,
             start_at,
-            cost: PathCost::new(start_at, &path_stops),
-            visited_sites: path_stops.iter().map(|stop| stop.site).collect(),
+            cost,
+            route_cost: cost,
+            visited_sites: path_stops
+                .iter()
+                .filter(|stop| stop.kind == StopKind::Visit)
+                .map(|stop| stop.site)
+                .collect(),
             stops: path_stops,
+            previous: None,
+            slack,
+            work_since_break: work,
+            total_work,
         })
     }
 
+    /// Recompute the ride durations for the current departure times. Return `false` if the
+    /// schedule is not feasible anymore
+    fn retime_rides(
+        world: &World,
+        traveler: TravelerId,
+        start_in: SiteId,
+        stops: &mut [Stop],
+    ) -> bool {
+        let mut prev_site = start_in;
+        let (mut work, mut total_work) = world[traveler].departure_work();
+
+        for stop in stops {
+            let ride = match world.ride(prev_site, stop.site, stop.ride_start) {
+                None => return false,
+                Some(ride) => ride,
+            };
+            stop.ride_end = stop.ride_start + ride;
+            if stop.ride_end > stop.service_start {
+                return false;
+            }
+
+            if let Some(rules) = &world.break_rules {
+                let service_time = stop.service_end - stop.service_start;
+                if stop.break_before_ride.is_some() {
+                    work = Duration::ZERO;
+                }
+                work += ride;
+                if work > rules.max_work_without_break {
+                    return false;
+                }
+                if stop.break_before_service {
+                    if stop.service_start - stop.ride_end < rules.min_break {
+                        return false;
+                    }
+                    work = Duration::ZERO;
+                }
+                work += service_time;
+                total_work += ride + service_time;
+                if work > rules.max_work_without_break
+                    || rules.max_total_work.is_some_and(|max| total_work > max)
+                {
+                    return false;
+                }
+            }
+
+            prev_site = stop.site;
+        }
+
+        true
+    }
+
+    /// Return the window in which the service must happen, if any, and when the service starts,
+    /// given the time the traveler is ready for it. Return `None` if there is no such window
+    fn schedule_service(
+        site: &Site,
+        stop: &StopSketch,
+        ready_at: Timestamp,
+    ) -> Option<(Option<BoundedTimeWindow>, Timestamp)> {
+        let service_window = match stop.duty {
+            Some(duty) => Some(duty),
+            None if stop.kind == StopKind::Visit && !site.opening_hours.is_empty() => {
+                Some(site.opening_after(ready_at)?)
+            }
+            None => None,
+        };
+        let service_start = match service_window {
+            Some(window) if window.start() > ready_at => window.start(),
+            _ => ready_at,
+        };
+        Some((service_window, service_start))
+    }
+
+    /// Chain this route after the path of the previous travelers
+    pub fn after(mut self, previous: Option<Arc<Path>>) -> Self {
+        if let Some(previous) = &previous {
+            self.cost = previous.cost + self.route_cost;
+            for site in previous.visited_sites.iter() {
+                self.visited_sites.insert(site);
+            }
+        }
+        self.previous = previous;
+        self
+    }
+
+    /// Iterate over the routes of all travelers, from the last one to the first one
+    pub fn routes(&self) -> impl Iterator<Item = &Path> {
+        std::iter::successors(Some(self), |path| path.previous.as_deref())
+    }
+
     pub fn end(&self) -> (SiteId, Timestamp) {
         match self.stops.last() {
             None => (self.start_in, self.start_at),
@@ -100,17 +363,26 @@ impl Path {
     #[cfg(test)]
     pub fn mock() -> Self {
         Path {
+            traveler: TravelerId::from(0),
             start_in: SiteId::from(0),
             start_at: Timestamp::from(0),
             stops: vec![],
             cost: PathCost::mock(),
-            visited_sites: BTreeSet::new(),
+            route_cost: PathCost::mock(),
+            visited_sites: SiteSet::new(),
+            previous: None,
+            slack: CappedMax::Max,
+            work_since_break: Duration::ZERO,
+            total_work: Duration::ZERO,
         }
     }
 }
 
 impl fmt::Display for Path {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(previous) = &self.previous {
+            write!(f, "{} | ", previous)?;
+        }
         write!(f, "{} @ {}", self.start_in, self.start_at)?;
         for stop in &self.stops {
             write!(f, "; {}", stop)?;
@@ -122,6 +394,7 @@ impl fmt::Display for Path {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cmp::Reverse;
 
     #[test]
     fn schedule() {
@@ -132,7 +405,7 @@ mod tests {
         site2.service_time = Duration::from(2);
 
         let mut world = World::mock(vec![site0, site1, site2]);
-        world.min_start_at = Timestamp::from(0);
+        world.travelers[0].min_start_at = Timestamp::from(0);
         world
             .ride_matrix
             .set(SiteId::from(0), SiteId::from(1), Duration::from(10));
@@ -142,15 +415,18 @@ mod tests {
 
         let path = Path::try_schedule(
             &world,
+            TravelerId::from(0),
             SiteId::from(0),
             &[
                 StopSketch {
                     site: SiteId::from(1),
                     duty: Some(BoundedTimeWindow::from((15, 18))),
+                    kind: StopKind::Visit,
                 },
                 StopSketch {
                     site: SiteId::from(2),
                     duty: Some(BoundedTimeWindow::from((39, 50))),
+                    kind: StopKind::Visit,
                 },
             ],
         )
@@ -165,4 +441,393 @@ mod tests {
         assert_eq!(path.stops[1].service_start, Timestamp::from(39));
         assert_eq!(path.stops[1].service_end, Timestamp::from(41));
     }
+
+    #[test]
+    fn chain_routes() {
+        let mut world = World::mock(vec![Site::mock(), Site::mock(), Site::mock()]);
+        world.travelers.push(Traveler::mock());
+        world.travelers[1].id = TravelerId::from(1);
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(10));
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(2), Duration::from(20));
+
+        let first = Path::try_schedule(
+            &world,
+            TravelerId::from(0),
+            SiteId::from(0),
+            &[StopSketch {
+                site: SiteId::from(1),
+                duty: None,
+                kind: StopKind::Visit,
+            }],
+        )
+        .unwrap();
+        let second = Path::try_schedule(
+            &world,
+            TravelerId::from(1),
+            SiteId::from(0),
+            &[StopSketch {
+                site: SiteId::from(2),
+                duty: None,
+                kind: StopKind::Visit,
+            }],
+        )
+        .unwrap()
+        .after(Some(Arc::new(first)));
+
+        assert_eq!(second.route_cost.total_ride, Duration::from(20));
+        assert_eq!(second.cost.total_ride, Duration::from(30));
+        assert_eq!(second.cost.stops, Reverse(2));
+        assert!(second.visited_sites.contains(SiteId::from(1)));
+        assert!(second.visited_sites.contains(SiteId::from(2)));
+        assert_eq!(second.routes().count(), 2);
+    }
+
+    #[test]
+    fn total_distance() {
+        let mut world = World::mock(vec![Site::mock(), Site::mock(), Site::mock()]);
+        let mut distance_matrix = DistanceMatrix::new(3);
+        for (from, to, distance) in [(0, 1, 1000), (1, 2, 500)] {
+            let (from, to) = (SiteId::from_usize(from), SiteId::from_usize(to));
+            world.ride_matrix.set(from, to, Duration::from(10));
+            distance_matrix.set(from, to, Distance::from(distance));
+        }
+        world.distance_matrix = Some(distance_matrix);
+
+        let visit = |site: usize| StopSketch {
+            site: SiteId::from(site),
+            duty: None,
+            kind: StopKind::Visit,
+        };
+        let first =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &[visit(1)]).unwrap();
+        assert_eq!(first.stops[0].ride_distance, Some(Distance::from(1000)));
+        assert_eq!(first.cost.total_distance, Distance::from(1000));
+
+        let path = Path::try_schedule(
+            &world,
+            TravelerId::from(0),
+            SiteId::from(0),
+            &[visit(1), visit(2)],
+        )
+        .unwrap();
+        assert_eq!(path.cost.total_distance, Distance::from(1500));
+
+        // Chained routes add up their distances
+        let second = Path::try_schedule(&world, TravelerId::from(0), SiteId::from(1), &[visit(2)])
+            .unwrap()
+            .after(Some(Arc::new(first)));
+        assert_eq!(second.cost.total_distance, Distance::from(1500));
+
+        // The sum of the longest rides does not overflow
+        let distance_matrix = world.distance_matrix.as_mut().unwrap();
+        for (from, to) in [(0, 1), (1, 2)] {
+            distance_matrix.set(
+                SiteId::from(from),
+                SiteId::from(to),
+                Distance::from(i32::MAX),
+            );
+        }
+        let path = Path::try_schedule(
+            &world,
+            TravelerId::from(0),
+            SiteId::from(0),
+            &[visit(1), visit(2)],
+        )
+        .unwrap();
+        assert_eq!(path.cost.total_distance.as_m(), 2 * i64::from(i32::MAX));
+    }
+
+    #[test]
+    fn collect_prizes() {
+        let mut world = World::mock((0..3).map(|_| Site::mock()).collect());
+        world.sites[0].prize = 100;
+        world.sites[1].prize = 10;
+        world.sites[2].prize = 3;
+        world.has_prizes = true;
+        for (from, to) in [(0, 1), (1, 2), (2, 0)] {
+            world.ride_matrix.set(
+                SiteId::from_usize(from),
+                SiteId::from_usize(to),
+                Duration::from(5),
+            );
+        }
+
+        let stop = |site: usize, kind| StopSketch {
+            site: SiteId::from(site),
+            duty: None,
+            kind,
+        };
+        // Returning to a site does not collect its prize
+        let path = Path::try_schedule(
+            &world,
+            TravelerId::from(0),
+            SiteId::from(0),
+            &[
+                stop(1, StopKind::Visit),
+                stop(2, StopKind::Visit),
+                stop(0, StopKind::Return),
+            ],
+        )
+        .unwrap();
+        let prizes: Vec<_> = path.stops.iter().map(|stop| stop.prize).collect();
+        assert_eq!(prizes, vec![10, 3, 0]);
+        assert_eq!(path.cost.total_prize, Reverse(13));
+
+        // Chained routes add up their prizes
+        let second = Path::try_schedule(
+            &world,
+            TravelerId::from(0),
+            SiteId::from(1),
+            &[stop(2, StopKind::Visit)],
+        )
+        .unwrap()
+        .after(Some(Arc::new(path)));
+        assert_eq!(second.cost.total_prize, Reverse(16));
+    }
+
+    #[test]
+    fn opening_hours() {
+        let site0 = Site::mock();
+        let mut site1 = Site::mock();
+        site1.service_time = Duration::from(5);
+        site1.opening_hours = vec![
+            BoundedTimeWindow::from((0, 12)),
+            BoundedTimeWindow::from((20, 30)),
+        ];
+
+        let mut world = World::mock(vec![site0, site1]);
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(10));
+        let visit = [StopSketch {
+            site: SiteId::from(1),
+            duty: None,
+            kind: StopKind::Visit,
+        }];
+
+        // The service does not fit before the first closing, so it waits for the next opening
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &visit).unwrap();
+        assert_eq!(path.stops[0].ride_end, Timestamp::from(20));
+        assert_eq!(path.stops[0].service_start, Timestamp::from(20));
+        assert_eq!(path.stops[0].service_end, Timestamp::from(25));
+
+        // The service does not fit before the last closing
+        world.travelers[0].min_start_at = Timestamp::from(16);
+        assert!(Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &visit).is_none());
+    }
+
+    #[test]
+    fn breaks() {
+        let mut sites = vec![Site::mock(), Site::mock(), Site::mock(), Site::mock()];
+        for site in &mut sites[1..] {
+            site.service_time = Duration::from(5);
+        }
+        let mut world = World::mock(sites);
+        world.break_rules = Some(BreakRules {
+            min_break: Duration::from(5),
+            max_work_without_break: Duration::from(20),
+            max_total_work: None,
+        });
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(10));
+        world
+            .ride_matrix
+            .set(SiteId::from(1), SiteId::from(2), Duration::from(10));
+        world
+            .ride_matrix
+            .set(SiteId::from(2), SiteId::from(3), Duration::from(5));
+        let stops = [
+            StopSketch {
+                site: SiteId::from(1),
+                duty: None,
+                kind: StopKind::Visit,
+            },
+            StopSketch {
+                site: SiteId::from(2),
+                duty: None,
+                kind: StopKind::Visit,
+            },
+            StopSketch {
+                site: SiteId::from(3),
+                duty: Some(BoundedTimeWindow::from((50, 60))),
+                kind: StopKind::Visit,
+            },
+        ];
+
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        // Too much work to ride to the second stop without a break
+        assert_eq!(path.stops[1].break_before_ride, Some(Duration::from(5)));
+        assert_eq!(path.stops[1].ride_start, Timestamp::from(20));
+        // Waiting for the duty counts as a break
+        assert!(path.stops[2].break_before_service);
+        assert_eq!(path.stops[2].ride_end, Timestamp::from(40));
+        assert_eq!(path.stops[2].service_start, Timestamp::from(50));
+        assert_eq!(path.cost.total_break, Duration::from(15));
+
+        // The work done before re-planning calls for a break before the first ride
+        world.travelers[0].current = Some(CurrentPosition {
+            site: SiteId::from(0),
+            at: Timestamp::from(0),
+            started_in: SiteId::from(0),
+            work_since_break: Duration::from(15),
+            total_work: Duration::from(15),
+        });
+        let replanned =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        assert_eq!(
+            replanned.stops[0].break_before_ride,
+            Some(Duration::from(5))
+        );
+        assert_eq!(replanned.stops[0].ride_start, Timestamp::from(5));
+        world.travelers[0].current = None;
+
+        // Too much work in total
+        world.break_rules.as_mut().unwrap().max_total_work = Some(Duration::from(35));
+        assert!(Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).is_none());
+    }
+
+    #[test]
+    fn time_dependent_rides() {
+        let site0 = Site::mock();
+        let mut site1 = Site::mock();
+        site1.service_time = Duration::from(1);
+        let site2 = Site::mock();
+
+        let mut world = World::mock(vec![site0, site1, site2]);
+        world
+            .ride_matrix
+            .set(SiteId::from(0), SiteId::from(1), Duration::from(10));
+        // Departing later takes longer
+        let profile = RideProfile::try_new(vec![
+            RideSample {
+                depart_at: Timestamp::from(0),
+                duration: Duration::from(10),
+            },
+            RideSample {
+                depart_at: Timestamp::from(100),
+                duration: Duration::from(60),
+            },
+        ])
+        .unwrap();
+        world
+            .ride_matrix
+            .set_profile(SiteId::from(1), SiteId::from(2), profile);
+        let stops = [
+            StopSketch {
+                site: SiteId::from(1),
+                duty: Some(BoundedTimeWindow::from((0, 100))),
+                kind: StopKind::Visit,
+            },
+            StopSketch {
+                site: SiteId::from(2),
+                duty: Some(BoundedTimeWindow::from((60, 100))),
+                kind: StopKind::Visit,
+            },
+        ];
+
+        // Departing at 11 takes 15, so the waiting could be compressed by delaying the first stop.
+        // But that would make the ride longer than the waiting, so the compression is discarded
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        assert_eq!(path.stops[1].ride_start, Timestamp::from(11));
+        assert_eq!(path.stops[1].ride_end, Timestamp::from(26));
+        assert_eq!(path.stops[1].service_start, Timestamp::from(60));
+
+        // The slack is the one of the schedule that is kept
+        let mut stops = stops;
+        stops[0].duty = Some(BoundedTimeWindow::from((0, 40)));
+        let path =
+            Path::try_schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        assert_eq!(path.stops[1].ride_start, Timestamp::from(11));
+        assert_eq!(path.slack, CappedMax::Value(Duration::from(29)));
+    }
+
+    #[test]
+    fn precedences() {
+        let mut world = World::mock((0..4).map(|_| Site::mock()).collect());
+        for from in 0..4 {
+            for to in 0..4 {
+                world.ride_matrix.set(
+                    SiteId::from_usize(from),
+                    SiteId::from_usize(to),
+                    Duration::from(10),
+                );
+            }
+        }
+        world.precedences = vec![
+            Precedence {
+                before: SiteId::from(1),
+                after: SiteId::from(2),
+                immediately: false,
+                within: Some(Duration::from(25)),
+            },
+            Precedence {
+                before: SiteId::from(1),
+                after: SiteId::from(3),
+                immediately: true,
+                within: None,
+            },
+        ];
+        let schedule = |world: &World, sites: &[usize]| {
+            let stops = sites
+                .iter()
+                .map(|&site| StopSketch {
+                    site: SiteId::from(site),
+                    duty: None,
+                    kind: StopKind::Visit,
+                })
+                .collect::<Vec<_>>();
+            Path::schedule(world, TravelerId::from(0), SiteId::from(0), &stops).map(|_| ())
+        };
+
+        assert_eq!(
+            schedule(&world, &[2, 1]),
+            Err(ScheduleError::PrecedenceBroken {
+                stop: 0,
+                before: SiteId::from(1)
+            })
+        );
+        assert_eq!(schedule(&world, &[1, 3, 2]), Ok(()));
+        // Too long after the first visit
+        assert_eq!(
+            schedule(&world, &[1, 3, 0, 2]),
+            Err(ScheduleError::PrecedenceBroken {
+                stop: 3,
+                before: SiteId::from(1)
+            })
+        );
+        // Not immediately after the first visit
+        assert!(schedule(&world, &[1, 2, 3]).is_err());
+
+        // The first service is delayed to wait less for the duty of the second one, which only
+        // meets the timing once the wait is compressed
+        world.sites[1].opening_hours = vec![BoundedTimeWindow::from((0, 200))];
+        world.sites[2].duties = vec![BoundedTimeWindow::from((100, 110))];
+        let stops = [
+            StopSketch {
+                site: SiteId::from(1),
+                duty: None,
+                kind: StopKind::Visit,
+            },
+            StopSketch {
+                site: SiteId::from(2),
+                duty: Some(BoundedTimeWindow::from((100, 110))),
+                kind: StopKind::Visit,
+            },
+        ];
+        let path = Path::schedule(&world, TravelerId::from(0), SiteId::from(0), &stops).unwrap();
+        assert_eq!(path.stops[0].service_end, Timestamp::from(90));
+        assert_eq!(path.stops[1].service_start, Timestamp::from(100));
+
+        // Completed sites meet the precedences
+        world.completed_sites = [SiteId::from(1)].into_iter().collect();
+        assert_eq!(schedule(&world, &[3, 2]), Ok(()));
+    }
 }
@@ -1,27 +1,43 @@
 use crate::models::*;
 use serde::{Deserialize, Serialize};
-use std::cmp::{Ordering, Reverse};
+use std::cmp::Reverse;
 use std::fmt;
+use std::ops::Add;
 
 #[derive(Debug, Clone, Eq, PartialEq, Copy, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PathCost {
     pub total_ride: Duration,
     pub total_time: Duration,
+    /// Zero when distances are not known
+    pub total_distance: Distance,
     pub stops_on_duty: Reverse<i32>,
     pub stops: Reverse<i32>,
+    pub total_prize: Reverse<i32>,
+    /// Informative only: this is already accounted for in `total_time`
+    pub total_break: Duration,
 }
 
 impl PathCost {
     pub fn new(start_at: Timestamp, stops: &[Stop]) -> Self {
         let mut total_ride = Duration::ZERO;
+        let mut total_distance = Distance::ZERO;
         let mut stops_on_duty = 0;
+        let mut visits = 0;
+        let mut total_prize = 0;
+        let mut total_break = Duration::ZERO;
 
         for stop in stops {
             total_ride += stop.ride_end - stop.ride_start;
+            total_distance += stop.ride_distance.unwrap_or(Distance::ZERO);
+            total_break += stop.total_break();
+            total_prize += stop.prize;
             if stop.duty.is_some() {
                 stops_on_duty += 1;
             }
+            if stop.kind == StopKind::Visit {
+                visits += 1;
+            }
         }
 
         let total_time = match stops.last() {
@@ -32,8 +48,11 @@ impl PathCost {
         PathCost {
             total_ride,
             total_time,
+            total_distance,
             stops_on_duty: Reverse(stops_on_duty),
-            stops: Reverse(stops.len() as i32),
+            stops: Reverse(visits),
+            total_prize: Reverse(total_prize),
+            total_break,
         }
     }
 
@@ -42,27 +61,27 @@ impl PathCost {
         PathCost {
             total_ride: Duration::ZERO,
             total_time: Duration::ZERO,
+            total_distance: Distance::ZERO,
             stops_on_duty: Reverse(0),
             stops: Reverse(0),
+            total_prize: Reverse(0),
+            total_break: Duration::ZERO,
         }
     }
 }
 
-impl PartialOrd for PathCost {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let ge = self.total_ride >= other.total_ride
-            && self.total_time >= other.total_time
-            && self.stops_on_duty >= other.stops_on_duty
-            && self.stops >= other.stops;
-        let le = self.total_ride <= other.total_ride
-            && self.total_time <= other.total_time
-            && self.stops_on_duty <= other.stops_on_duty
-            && self.stops <= other.stops;
-        match (ge, le) {
-            (true, true) => Some(Ordering::Equal),
-            (true, false) => Some(Ordering::Greater),
-            (false, true) => Some(Ordering::Less),
-            (false, false) => None,
+impl Add for PathCost {
+    type Output = PathCost;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        PathCost {
+            total_ride: self.total_ride + rhs.total_ride,
+            total_time: self.total_time + rhs.total_time,
+            total_distance: self.total_distance + rhs.total_distance,
+            stops_on_duty: Reverse(self.stops_on_duty.0 + rhs.stops_on_duty.0),
+            stops: Reverse(self.stops.0 + rhs.stops.0),
+            total_prize: Reverse(self.total_prize.0 + rhs.total_prize.0),
+            total_break: self.total_break + rhs.total_break,
         }
     }
 }
@@ -71,8 +90,14 @@ impl fmt::Display for PathCost {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{{total_ride: {}, total_time: {}, stops_on_duty: {}, stops: {}}}",
-            self.total_ride, self.total_time, self.stops_on_duty.0, self.stops.0
+            "{{total_ride: {}, total_time: {}, total_distance: {}, stops_on_duty: {}, stops: {}, total_prize: {}, total_break: {}}}",
+            self.total_ride,
+            self.total_time,
+            self.total_distance,
+            self.stops_on_duty.0,
+            self.stops.0,
+            self.total_prize.0,
+            self.total_break
         )
     }
 }
@@ -0,0 +1,60 @@
+use crate::models::*;
+use anyhow::Result;
+
+/// Requires any visit to `after` to follow a visit to `before` in the same route
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precedence {
+    pub before: SiteId,
+    pub after: SiteId,
+    /// Whether `before` must be the stop right before `after`
+    pub immediately: bool,
+    /// The longest time from the end of the service at `before` to the start of the service at
+    /// `after`
+    pub within: Option<Duration>,
+}
+
+impl Precedence {
+    pub fn try_from_json(sites: &IdConverter<SiteId>, input: &input::Precedence) -> Result<Self> {
+        Ok(Precedence {
+            before: sites.get(&input.before)?,
+            after: sites.get(&input.after)?,
+            immediately: input.immediately,
+            within: input.within,
+        })
+    }
+
+    /// Whether `after` can follow the given stops of the route. Sites completed before re-planning
+    /// meet any precedence
+    pub fn is_ordered(&self, completed_sites: &SiteSet, stops: &[Stop]) -> bool {
+        completed_sites.contains(self.before)
+            || match self.position(stops) {
+                None => false,
+                Some(position) => !self.immediately || position + 1 == stops.len(),
+            }
+    }
+
+    /// Like [`Precedence::is_ordered`], also checking when the service at `after` starts
+    pub fn is_met(
+        &self,
+        completed_sites: &SiteSet,
+        stops: &[Stop],
+        service_start: Timestamp,
+    ) -> bool {
+        if completed_sites.contains(self.before) {
+            return true;
+        }
+        self.is_ordered(completed_sites, stops)
+            && match (self.within, self.position(stops)) {
+                (Some(within), Some(position)) => {
+                    service_start <= stops[position].service_end + within
+                }
+                _ => true,
+            }
+    }
+
+    fn position(&self, stops: &[Stop]) -> Option<usize> {
+        stops
+            .iter()
+            .rposition(|stop| stop.site == self.before && stop.kind == StopKind::Visit)
+    }
+}
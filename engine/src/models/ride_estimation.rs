@@ -0,0 +1,132 @@
+use crate::models::*;
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+/// How to estimate the rides that are not given, from the coordinates of the sites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RideEstimation {
+    /// Consecutive distance bands, each ridden at its own average speed. Only the last one has no
+    /// upper bound
+    pub speeds: Vec<SpeedBand>,
+    /// Multiplies the great-circle distance to approximate the road distance. Defaults to 1.3
+    pub detour_factor: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpeedBand {
+    pub up_to: Option<Distance>,
+    pub km_per_hour: f64,
+}
+
+const DEFAULT_DETOUR_FACTOR: f64 = 1.3;
+
+impl RideEstimation {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(!self.speeds.is_empty(), "At least one speed band is needed");
+        let mut previous_up_to = Distance::ZERO;
+        for (i, band) in self.speeds.iter().enumerate() {
+            ensure!(band.km_per_hour > 0.0, "Speeds must be positive");
+            match band.up_to {
+                Some(up_to) => {
+                    ensure!(
+                        i + 1 < self.speeds.len(),
+                        "The last speed band must have no upper bound"
+                    );
+                    ensure!(
+                        up_to > previous_up_to,
+                        "Speed bands must be in increasing distance"
+                    );
+                    previous_up_to = up_to;
+                }
+                None => ensure!(
+                    i + 1 == self.speeds.len(),
+                    "Only the last speed band can have no upper bound"
+                ),
+            }
+        }
+        ensure!(
+            self.detour_factor.unwrap_or(DEFAULT_DETOUR_FACTOR) >= 1.0,
+            "The detour factor must be at least 1"
+        );
+        Ok(())
+    }
+
+    /// Estimate the road distance and the ride duration between two places
+    pub fn estimate(&self, from: Coordinates, to: Coordinates) -> (Distance, Duration) {
+        let detour_factor = self.detour_factor.unwrap_or(DEFAULT_DETOUR_FACTOR);
+        let road_m = from.distance_to(to).as_m() as f64 * detour_factor;
+
+        let mut seconds = 0.0;
+        let mut band_start_m = 0.0;
+        for band in &self.speeds {
+            let band_end_m = band
+                .up_to
+                .map_or(f64::INFINITY, |up_to| up_to.as_m() as f64);
+            let ridden_m = road_m.min(band_end_m) - band_start_m;
+            if ridden_m <= 0.0 {
+                break;
+            }
+            seconds += ridden_m / (band.km_per_hour / 3.6);
+            band_start_m = band_end_m;
+        }
+
+        (
+            Distance::from_m(road_m.round() as i32),
+            Duration::from_s(seconds.round() as i32),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate() {
+        let paris = Coordinates {
+            latitude: 48.8566,
+            longitude: 2.3522,
+        };
+        let london = Coordinates {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        assert_eq!(paris.distance_to(london).as_m() / 1000, 343);
+
+        // 10km at 36km/h, then 4km at 72km/h
+        let estimation = RideEstimation {
+            speeds: vec![
+                SpeedBand {
+                    up_to: Some(Distance::from_m(10_000)),
+                    km_per_hour: 36.0,
+                },
+                SpeedBand {
+                    up_to: None,
+                    km_per_hour: 72.0,
+                },
+            ],
+            detour_factor: Some(1.4),
+        };
+        estimation.validate().unwrap();
+        let east = Coordinates {
+            latitude: 0.0,
+            longitude: 10_000.0 / EARTH_RADIUS_DEGREE_M,
+        };
+        let origin = Coordinates {
+            latitude: 0.0,
+            longitude: 0.0,
+        };
+        let (distance, duration) = estimation.estimate(origin, east);
+        assert_eq!(distance, Distance::from_m(14_000));
+        assert_eq!(duration, Duration::from_s(1000 + 200));
+
+        let mut invalid = estimation.clone();
+        invalid.speeds.reverse();
+        assert!(invalid.validate().is_err());
+    }
+
+    /// The length of one degree of longitude at the equator
+    const EARTH_RADIUS_DEGREE_M: f64 = 6_371_000.0 * std::f64::consts::PI / 180.0;
+}
@@ -1,26 +1,69 @@
 use crate::models::*;
 
+/// A dense square matrix with the ride duration between each pair of sites
+///
+/// Entries are stored as plain seconds, with sentinels for missing rides and for rides that follow
+/// a time-dependent profile, to keep the matrix compact when there are thousands of sites.
 #[derive(Debug, Clone)]
 pub struct RideMatrix {
     side: usize,
-    entries: Vec<Option<Duration>>,
+    entries: Vec<i32>,
+    profiles: Vec<RideProfile>,
 }
 
+const MISSING: i32 = -1;
+/// Entries below this value point to `profiles[FIRST_PROFILE - entry]`
+const FIRST_PROFILE: i32 = -2;
+
 impl RideMatrix {
     /// Create a matrix with the diagonal as zero and the other entries as `None`
     pub fn new(side: usize) -> Self {
-        let mut entries = vec![None; side * side];
+        let mut entries = vec![MISSING; side * side];
         for i in 0..side {
-            entries[i * side + i] = Some(Duration::ZERO);
+            entries[i * side + i] = 0;
+        }
+        RideMatrix {
+            side,
+            entries,
+            profiles: vec![],
+        }
+    }
+
+    /// The ride duration when departing at the given time
+    pub fn get(&self, from: SiteId, to: SiteId, depart_at: Timestamp) -> Option<Duration> {
+        match self.entries[self.index(from, to)] {
+            MISSING => None,
+            s if s <= FIRST_PROFILE => {
+                Some(self.profiles[(FIRST_PROFILE - s) as usize].get(depart_at))
+            }
+            s => Some(Duration::from_s(s)),
         }
-        RideMatrix { side, entries }
     }
 
-    pub fn get(&self, from: SiteId, to: SiteId) -> Option<Duration> {
-        self.entries[from.as_usize() * self.side + to.as_usize()]
+    /// Whether there is a ride, at least at some time
+    pub fn contains(&self, from: SiteId, to: SiteId) -> bool {
+        self.entries[self.index(from, to)] != MISSING
     }
 
+    /// Panics if `value` is negative
     pub fn set(&mut self, from: SiteId, to: SiteId, value: Duration) {
-        self.entries[from.as_usize() * self.side + to.as_usize()] = Some(value);
+        assert!(value >= Duration::ZERO, "ride durations cannot be negative");
+        let index = self.index(from, to);
+        self.entries[index] = value.as_s();
+    }
+
+    pub fn set_profile(&mut self, from: SiteId, to: SiteId, profile: RideProfile) {
+        let index = self.index(from, to);
+        self.entries[index] = FIRST_PROFILE - self.profiles.len() as i32;
+        self.profiles.push(profile);
+    }
+
+    /// Whether any ride depends on the departure time
+    pub fn is_time_dependent(&self) -> bool {
+        !self.profiles.is_empty()
+    }
+
+    fn index(&self, from: SiteId, to: SiteId) -> usize {
+        from.as_usize() * self.side + to.as_usize()
     }
 }
@@ -0,0 +1,91 @@
+use crate::models::*;
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+/// The ride duration when departing at a given time
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RideSample {
+    pub depart_at: Timestamp,
+    pub duration: Duration,
+}
+
+/// A ride duration that depends on the departure time
+///
+/// Durations are linearly interpolated between samples and constant before the first sample and
+/// after the last one. The profile is FIFO-consistent: departing later never arrives sooner.
+#[derive(Debug, Clone)]
+pub struct RideProfile {
+    samples: Vec<RideSample>,
+}
+
+impl RideProfile {
+    pub fn try_new(mut samples: Vec<RideSample>) -> Result<Self> {
+        ensure!(
+            !samples.is_empty(),
+            "A ride profile needs at least one sample"
+        );
+        samples.sort_by_key(|sample| sample.depart_at);
+        for (a, b) in samples.iter().zip(&samples[1..]) {
+            ensure!(
+                a.depart_at != b.depart_at,
+                "Repeated ride profile sample at {}",
+                a.depart_at
+            );
+            ensure!(
+                a.depart_at + a.duration <= b.depart_at + b.duration,
+                "Departing at {} would arrive sooner than departing at {}",
+                b.depart_at,
+                a.depart_at
+            );
+        }
+        Ok(RideProfile { samples })
+    }
+
+    pub fn get(&self, depart_at: Timestamp) -> Duration {
+        let next = self
+            .samples
+            .partition_point(|sample| sample.depart_at <= depart_at);
+        if next == 0 {
+            return self.samples[0].duration;
+        } else if next == self.samples.len() {
+            return self.samples[next - 1].duration;
+        }
+
+        let a = self.samples[next - 1];
+        let b = self.samples[next];
+        let elapsed = (depart_at - a.depart_at).as_s() as i64;
+        let span = (b.depart_at - a.depart_at).as_s() as i64;
+        let delta = (b.duration - a.duration).as_s() as i64;
+        a.duration + Duration::from_s((delta * elapsed / span) as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(depart_at: i32, duration: i32) -> RideSample {
+        RideSample {
+            depart_at: Timestamp::from(depart_at),
+            duration: Duration::from(duration),
+        }
+    }
+
+    #[test]
+    fn interpolate() {
+        let profile =
+            RideProfile::try_new(vec![sample(200, 10), sample(100, 10), sample(150, 60)]).unwrap();
+        assert_eq!(profile.get(Timestamp::from(0)), Duration::from(10));
+        assert_eq!(profile.get(Timestamp::from(125)), Duration::from(35));
+        assert_eq!(profile.get(Timestamp::from(150)), Duration::from(60));
+        assert_eq!(profile.get(Timestamp::from(175)), Duration::from(35));
+        assert_eq!(profile.get(Timestamp::from(300)), Duration::from(10));
+    }
+
+    #[test]
+    fn reject_overtaking() {
+        assert!(RideProfile::try_new(vec![sample(100, 60), sample(110, 40)]).is_err());
+        assert!(RideProfile::try_new(vec![sample(100, 60), sample(110, 50)]).is_ok());
+    }
+}
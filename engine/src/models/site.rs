@@ -1,5 +1,5 @@
 use crate::models::*;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
@@ -9,7 +9,11 @@ pub struct Site {
     pub duties: Vec<BoundedTimeWindow>,
     pub service_time: Duration,
     pub visit: Visit,
-    pub can_start_here: bool,
+    /// Collected when the site is visited
+    pub prize: i32,
+    /// Sorted windows when visits without duty can be serviced. When empty, the site is always open
+    pub opening_hours: Vec<BoundedTimeWindow>,
+    pub coordinates: Option<Coordinates>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
@@ -22,16 +26,38 @@ pub enum Visit {
 
 impl Site {
     pub fn try_from_json(sites: &IdConverter<SiteId>, input: input::Site) -> Result<Self> {
+        let mut opening_hours = input.opening_hours;
+        opening_hours.sort_by_key(|window| window.start());
+
+        let coordinates = match (input.latitude, input.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Coordinates {
+                latitude,
+                longitude,
+            }),
+            (None, None) => None,
+            _ => bail!("Site {} must have both latitude and longitude", input.name),
+        };
+
         Ok(Site {
             id: sites.get(&input.name)?,
             name: input.name,
             duties: input.duties,
             service_time: input.service_time,
             visit: input.visit,
-            can_start_here: input.can_start_here,
+            prize: input.prize,
+            opening_hours,
+            coordinates,
         })
     }
 
+    /// Return the first opening window in which a service starting no sooner than `arrival` fits
+    pub fn opening_after(&self, arrival: Timestamp) -> Option<BoundedTimeWindow> {
+        self.opening_hours
+            .iter()
+            .copied()
+            .find(|window| window.start().max(arrival) + self.service_time <= window.end())
+    }
+
     #[cfg(test)]
     pub fn mock() -> Self {
         Site {
@@ -40,7 +66,9 @@ impl Site {
             duties: vec![],
             service_time: Duration::ZERO,
             visit: Visit::Always,
-            can_start_here: false,
+            prize: 0,
+            opening_hours: vec![],
+            coordinates: None,
         }
     }
 }
@@ -0,0 +1,117 @@
+use crate::models::*;
+use std::fmt;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of sites, represented as a bitmap indexed by the site id
+///
+/// The bitmap only grows as high as the greatest inserted id, so that two sets with the same sites
+/// always have the same representation.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct SiteSet {
+    words: Vec<u64>,
+}
+
+impl SiteSet {
+    pub fn new() -> Self {
+        SiteSet::default()
+    }
+
+    pub fn contains(&self, site: SiteId) -> bool {
+        let (word, bit) = Self::position(site);
+        self.words.get(word).is_some_and(|&w| w & bit != 0)
+    }
+
+    /// Returns `true` if the site was not yet present
+    pub fn insert(&mut self, site: SiteId) -> bool {
+        let (word, bit) = Self::position(site);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let is_new = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        is_new
+    }
+
+    pub fn is_superset(&self, other: &SiteSet) -> bool {
+        other.words.iter().enumerate().all(|(i, &other_word)| {
+            let self_word = self.words.get(i).copied().unwrap_or(0);
+            other_word & !self_word == 0
+        })
+    }
+
+    pub fn union_with(&mut self, other: &SiteSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (self_word, &other_word) in self.words.iter_mut().zip(&other.words) {
+            *self_word |= other_word;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SiteId> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..WORD_BITS)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| SiteId::from_usize(i * WORD_BITS + bit))
+        })
+    }
+
+    fn position(site: SiteId) -> (usize, u64) {
+        let i = site.as_usize();
+        (i / WORD_BITS, 1 << (i % WORD_BITS))
+    }
+}
+
+impl FromIterator<SiteId> for SiteSet {
+    fn from_iter<T: IntoIterator<Item = SiteId>>(iter: T) -> Self {
+        let mut set = SiteSet::new();
+        for site in iter {
+            set.insert(site);
+        }
+        set
+    }
+}
+
+impl fmt::Debug for SiteSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn set_operations() {
+        let small: SiteSet = [3, 70].into_iter().map(SiteId::from).collect();
+        let mut large: SiteSet = [3, 1000].into_iter().map(SiteId::from).collect();
+
+        assert!(large.contains(SiteId::from(1000)));
+        assert!(!large.contains(SiteId::from(70)));
+        assert!(!small.contains(SiteId::from(1000)));
+        assert!(!large.is_superset(&small));
+
+        assert!(large.insert(SiteId::from(70)));
+        assert!(!large.insert(SiteId::from(70)));
+        assert!(large.is_superset(&small));
+        assert!(!small.is_superset(&large));
+        assert!(small.is_superset(&SiteSet::new()));
+
+        let mut union = small.clone();
+        union.union_with(&large);
+        assert!(union.is_superset(&large));
+        assert!(union.is_superset(&small));
+
+        assert_eq!(
+            large.iter().map(|site| site.as_usize()).collect_vec(),
+            vec![3, 70, 1000]
+        );
+    }
+}
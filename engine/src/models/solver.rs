@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// How the paths are searched for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Solver {
+    /// Beam-like search, bounded by `max_tested_extensions` and `max_bag_items`
+    #[default]
+    Heuristic,
+    /// Return the true Pareto front. Only practical for small worlds
+    Exact,
+}
+
+impl Solver {
+    /// The maximum number of visitable sites accepted by `Solver::Exact`
+    pub const MAX_EXACT_SITES: usize = 20;
+}
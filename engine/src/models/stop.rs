@@ -1,27 +1,56 @@
 use crate::models::*;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 #[derive(Debug, Clone, Copy)]
 pub struct StopSketch {
     pub site: SiteId,
     pub duty: Option<BoundedTimeWindow>,
+    pub kind: StopKind,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StopKind {
+    /// The site is serviced
+    Visit,
+    /// The traveler only rides to the site to end the route there
+    Return,
 }
 
 #[derive(Debug, Clone)]
 pub struct Stop {
     pub site: SiteId,
     pub duty: Option<BoundedTimeWindow>,
+    pub kind: StopKind,
     pub ride_start: Timestamp,
     pub ride_end: Timestamp,
     pub service_start: Timestamp,
     pub service_end: Timestamp,
+    /// `None` when the distance of the ride is not known
+    pub ride_distance: Option<Distance>,
+    /// The prize collected by this stop
+    pub prize: i32,
+    /// A break taken at the previous site, just before the ride starts
+    pub break_before_ride: Option<Duration>,
+    /// Whether the whole waiting before the service counts as a break
+    pub break_before_service: bool,
 }
 
 impl Stop {
+    pub fn total_break(&self) -> Duration {
+        let mut total = self.break_before_ride.unwrap_or(Duration::ZERO);
+        if self.break_before_service {
+            total += self.service_start - self.ride_end;
+        }
+        total
+    }
+
     pub fn sketch(&self) -> StopSketch {
         StopSketch {
             site: self.site,
             duty: self.duty,
+            kind: self.kind,
         }
     }
 }
@@ -29,6 +58,9 @@ impl Stop {
 impl fmt::Display for Stop {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.duty {
+            None if self.kind == StopKind::Return => {
+                write!(f, "return to {} @ {}", self.site, self.ride_end)
+            }
             None => {
                 write!(
                     f,
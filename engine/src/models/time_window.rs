@@ -4,7 +4,7 @@ use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt;
 
 /// A non-empty time window, bounded in both sides
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct BoundedTimeWindow {
     /// Inclusive
     start: Timestamp,
@@ -12,19 +12,6 @@ pub struct BoundedTimeWindow {
     end: Timestamp,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct LeftBoundedTimeWindow {
-    pub start: Timestamp,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct RightBoundedTimeWindow {
-    pub end: Timestamp,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct UnboundedTimeWindow;
-
 impl BoundedTimeWindow {
     pub fn try_new(start: Timestamp, end: Timestamp) -> Result<Self> {
         ensure!(end >= start);
@@ -46,24 +33,6 @@ impl fmt::Display for BoundedTimeWindow {
     }
 }
 
-impl fmt::Display for LeftBoundedTimeWindow {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[{}, ∞]", self.start)
-    }
-}
-
-impl fmt::Display for RightBoundedTimeWindow {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[-∞, {}]", self.end)
-    }
-}
-
-impl fmt::Display for UnboundedTimeWindow {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[-∞, ∞]")
-    }
-}
-
 impl<'de> Deserialize<'de> for BoundedTimeWindow {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
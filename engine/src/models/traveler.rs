@@ -0,0 +1,163 @@
+use crate::models::*;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// A person that follows one of the routes of a path
+#[derive(Debug, Clone)]
+pub struct Traveler {
+    pub id: TravelerId,
+    pub name: String,
+    /// The sites where this traveler can start from
+    pub start_sites: Vec<SiteId>,
+    pub min_start_at: Timestamp,
+    pub max_end_at: Option<Timestamp>,
+    pub end: PathEnd,
+    /// The sites where this traveler can end at, when `end` is `PathEnd::EndSite`
+    pub end_sites: Vec<SiteId>,
+    /// Where the traveler is now, when re-planning
+    pub current: Option<CurrentPosition>,
+}
+
+/// The state of a traveler in the middle of the day
+#[derive(Debug, Clone, Copy)]
+pub struct CurrentPosition {
+    pub site: SiteId,
+    pub at: Timestamp,
+    /// Where the route started, to return to it
+    pub started_in: SiteId,
+    /// Work since the last break, only used with break rules
+    pub work_since_break: Duration,
+    /// Work on the route so far, only used with break rules
+    pub total_work: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PathEnd {
+    /// The route ends wherever the last visit was
+    #[default]
+    Anywhere,
+    /// The route must end at one of the end sites, riding to one of them if needed
+    EndSite,
+    /// The route must end where it started, riding back if needed
+    Start,
+}
+
+impl Traveler {
+    /// The name of the traveler created when the input does not list any
+    pub const DEFAULT_NAME: &'static str = "default";
+
+    pub fn try_from_json(
+        sites: &IdConverter<SiteId>,
+        travelers: &IdConverter<TravelerId>,
+        world: &input::World,
+        input: input::Traveler,
+    ) -> Result<Self> {
+        let start_sites = if input.start_sites.is_empty() {
+            Self::default_start_sites(sites, world)?
+        } else {
+            Self::get_sites(sites, &input.start_sites)?
+        };
+        let end_sites = if input.end_sites.is_empty() {
+            Self::default_end_sites(sites, world)?
+        } else {
+            Self::get_sites(sites, &input.end_sites)?
+        };
+
+        Ok(Traveler {
+            id: travelers.get(&input.name)?,
+            name: input.name,
+            start_sites,
+            min_start_at: input.min_start_at.unwrap_or(world.min_start_at),
+            max_end_at: input.max_end_at.or(world.max_end_at),
+            end: world.path_end,
+            end_sites,
+            current: None,
+        })
+    }
+
+    /// Build the single traveler used when the input does not list any
+    pub fn default_from_json(sites: &IdConverter<SiteId>, world: &input::World) -> Result<Self> {
+        Ok(Traveler {
+            id: TravelerId::from_usize(0),
+            name: Self::DEFAULT_NAME.to_owned(),
+            start_sites: Self::default_start_sites(sites, world)?,
+            min_start_at: world.min_start_at,
+            max_end_at: world.max_end_at,
+            end: world.path_end,
+            end_sites: Self::default_end_sites(sites, world)?,
+            current: None,
+        })
+    }
+
+    /// The sites where new routes start: the current site when re-planning
+    pub fn departure_sites(&self) -> &[SiteId] {
+        match &self.current {
+            Some(current) => std::slice::from_ref(&current.site),
+            None => &self.start_sites,
+        }
+    }
+
+    /// When new routes start: the current time when re-planning
+    pub fn departure_at(&self) -> Timestamp {
+        match &self.current {
+            Some(current) => current.at,
+            None => self.min_start_at,
+        }
+    }
+
+    /// The work since the last break and on the whole route when new routes start
+    pub fn departure_work(&self) -> (Duration, Duration) {
+        match &self.current {
+            Some(current) => (current.work_since_break, current.total_work),
+            None => (Duration::ZERO, Duration::ZERO),
+        }
+    }
+
+    /// Where a route that departed from `start_in` returns to, when it must end where it started
+    pub fn return_site(&self, start_in: SiteId) -> SiteId {
+        match &self.current {
+            Some(current) => current.started_in,
+            None => start_in,
+        }
+    }
+
+    fn default_start_sites(
+        sites: &IdConverter<SiteId>,
+        world: &input::World,
+    ) -> Result<Vec<SiteId>> {
+        world
+            .sites
+            .iter()
+            .filter(|site| site.can_start_here)
+            .map(|site| sites.get(&site.name))
+            .collect()
+    }
+
+    fn default_end_sites(sites: &IdConverter<SiteId>, world: &input::World) -> Result<Vec<SiteId>> {
+        world
+            .sites
+            .iter()
+            .filter(|site| site.can_end_here)
+            .map(|site| sites.get(&site.name))
+            .collect()
+    }
+
+    fn get_sites(sites: &IdConverter<SiteId>, names: &[String]) -> Result<Vec<SiteId>> {
+        names.iter().map(|name| sites.get(name)).collect()
+    }
+
+    #[cfg(test)]
+    pub fn mock() -> Self {
+        Traveler {
+            id: TravelerId::from_usize(0),
+            name: String::new(),
+            start_sites: vec![],
+            min_start_at: Timestamp::mock(),
+            max_end_at: None,
+            end: PathEnd::Anywhere,
+            end_sites: vec![],
+            current: None,
+        }
+    }
+}
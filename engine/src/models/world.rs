@@ -1,29 +1,117 @@
 use crate::models::*;
-use anyhow::Result;
+use anyhow::{bail, ensure, Context, Result};
 use itertools::Itertools;
-use std::collections::BTreeSet;
 use std::ops::Index;
 
 #[derive(Debug, Clone)]
 pub struct World {
-    pub sites: Vec<Site>,
-    pub min_start_at: Timestamp,
-    pub max_end_at: Option<Timestamp>,
-    pub ride_matrix: RideMatrix,
-    pub must_visit: BTreeSet<SiteId>,
+    pub(crate) sites: Vec<Site>,
+    pub(crate) travelers: Vec<Traveler>,
+    pub(crate) ride_matrix: RideMatrix,
+    pub(crate) distance_matrix: Option<DistanceMatrix>,
+    /// For each site, the sites to which the ride was estimated from coordinates
+    pub(crate) estimated_rides: Vec<SiteSet>,
+    pub(crate) must_visit: SiteSet,
+    /// The sites visited before re-planning. They are not visited again, nor in `must_visit`
+    pub(crate) completed_sites: SiteSet,
+    pub(crate) precedences: Vec<Precedence>,
+    pub(crate) break_rules: Option<BreakRules>,
+    pub(crate) objectives: Objectives,
+    /// Whether any site has a prize
+    pub(crate) has_prizes: bool,
+    pub(crate) solver: Solver,
     // Heuristics parametrization
     /// The maximum number of extensions to test from each base path during the initial build phase
-    pub max_tested_extensions: usize,
+    pub(crate) max_tested_extensions: usize,
     /// The maximum of paths to consider at any time
-    pub max_bag_items: usize,
+    pub(crate) max_bag_items: usize,
     /// The maximum number of results to return
-    pub max_results: usize,
+    pub(crate) max_results: usize,
+    /// The maximum number of local search moves applied to each finished path
+    pub(crate) max_improvement_steps: usize,
+    /// Stop searching after this wall-clock time
+    pub(crate) time_limit: Option<std::time::Duration>,
+    /// How many threads to search with, where 0 means one per CPU core
+    pub(crate) threads: usize,
 }
 
 impl World {
-    pub fn try_from_json(input: input::World) -> Result<Self> {
+    pub fn try_from_json(mut input: input::World) -> Result<Self> {
         let sites = IdConverter::new(input.sites.iter().map(|site| site.name.clone()))?;
 
+        let input_travelers = std::mem::take(&mut input.travelers);
+        let mut travelers = if input_travelers.is_empty() {
+            vec![Traveler::default_from_json(&sites, &input)?]
+        } else {
+            let traveler_ids =
+                IdConverter::new(input_travelers.iter().map(|traveler| traveler.name.clone()))?;
+            input_travelers
+                .into_iter()
+                .map(|traveler| Traveler::try_from_json(&sites, &traveler_ids, &input, traveler))
+                .try_collect()?
+        };
+
+        for traveler in &travelers {
+            ensure!(
+                traveler.end != PathEnd::EndSite || !traveler.end_sites.is_empty(),
+                "Traveler {} has no end site: no site can end here",
+                traveler.name
+            );
+        }
+
+        // Continue from the current position of each traveler
+        let mut completed_sites = SiteSet::new();
+        if let Some(replan) = &input.replan {
+            for position in &replan.positions {
+                let traveler = match &position.traveler {
+                    Some(name) => travelers
+                        .iter_mut()
+                        .find(|traveler| &traveler.name == name)
+                        .with_context(|| format!("Could not find traveler {}", name))?,
+                    None if travelers.len() == 1 => &mut travelers[0],
+                    None => bail!("Positions must name their traveler"),
+                };
+                let site = sites.get(&position.site)?;
+                let started_in = match (&position.started_in, &traveler.start_sites[..]) {
+                    (Some(name), _) => sites.get(name)?,
+                    (None, &[start_site]) => start_site,
+                    (None, _) if traveler.end != PathEnd::Start => site,
+                    (None, _) => bail!(
+                        "The position of {} must state where it started",
+                        traveler.name
+                    ),
+                };
+                let total_work = position.total_work.unwrap_or(Duration::ZERO);
+                let work_since_break = match position.last_break_end {
+                    Some(last_break_end) => {
+                        ensure!(
+                            last_break_end <= position.at,
+                            "The last break of {} ends after its position",
+                            traveler.name
+                        );
+                        total_work.min(position.at - last_break_end)
+                    }
+                    None => total_work,
+                };
+                traveler.current = Some(CurrentPosition {
+                    site,
+                    at: position.at,
+                    started_in,
+                    work_since_break,
+                    total_work,
+                });
+            }
+            for name in &replan.completed_sites {
+                completed_sites.insert(sites.get(name)?);
+            }
+        }
+
+        let precedences = input
+            .precedences
+            .iter()
+            .map(|precedence| Precedence::try_from_json(&sites, precedence))
+            .try_collect()?;
+
         let mut ride_matrix = RideMatrix::new(input.sites.len());
         for from_site in &input.sites {
             let from_site_id = sites.get(&from_site.name)?;
@@ -31,6 +119,27 @@ impl World {
                 let to_site_id = sites.get(to_site)?;
                 ride_matrix.set(from_site_id, to_site_id, duration);
             }
+            for (to_site, samples) in &from_site.ride_profiles {
+                let to_site_id = sites.get(to_site)?;
+                let profile = RideProfile::try_new(samples.clone()).with_context(|| {
+                    format!(
+                        "Invalid ride profile from {} to {}",
+                        from_site.name, to_site
+                    )
+                })?;
+                ride_matrix.set_profile(from_site_id, to_site_id, profile);
+            }
+        }
+
+        let mut distance_matrix = None;
+        for from_site in &input.sites {
+            let from_site_id = sites.get(&from_site.name)?;
+            for (to_site, &distance) in &from_site.ride_distances {
+                let to_site_id = sites.get(to_site)?;
+                distance_matrix
+                    .get_or_insert_with(|| DistanceMatrix::new(input.sites.len()))
+                    .set(from_site_id, to_site_id, distance);
+            }
         }
 
         let sites: Vec<_> = input
@@ -38,28 +147,127 @@ impl World {
             .into_iter()
             .map(|site| Site::try_from_json(&sites, site))
             .try_collect()?;
+
+        let mut estimated_rides = vec![];
+        if let Some(estimation) = &input.ride_estimation {
+            estimation.validate().context("Invalid ride estimation")?;
+            estimated_rides = vec![SiteSet::new(); sites.len()];
+            for from_site in &sites {
+                for to_site in &sites {
+                    let (Some(from), Some(to)) = (from_site.coordinates, to_site.coordinates)
+                    else {
+                        continue;
+                    };
+                    if ride_matrix.contains(from_site.id, to_site.id) {
+                        continue;
+                    }
+
+                    let (distance, ride) = estimation.estimate(from, to);
+                    ride_matrix.set(from_site.id, to_site.id, ride);
+                    estimated_rides[from_site.id.as_usize()].insert(to_site.id);
+                    if let Some(distance_matrix) = &mut distance_matrix {
+                        if distance_matrix.get(from_site.id, to_site.id).is_none() {
+                            distance_matrix.set(from_site.id, to_site.id, distance);
+                        }
+                    }
+                }
+            }
+        }
+        // A partial total distance would be misleading, so every ride must have a distance
+        if let Some(distance_matrix) = &distance_matrix {
+            for from_site in &sites {
+                for to_site in &sites {
+                    ensure!(
+                        from_site.id == to_site.id
+                            || !ride_matrix.contains(from_site.id, to_site.id)
+                            || distance_matrix.get(from_site.id, to_site.id).is_some(),
+                        "Missing ride distance from {} to {}",
+                        from_site.name,
+                        to_site.name
+                    );
+                }
+            }
+        }
+
+        let visitable_sites = sites
+            .iter()
+            .filter(|site| site.visit != Visit::Never)
+            .count();
+        ensure!(
+            input.solver != Solver::Exact || visitable_sites <= Solver::MAX_EXACT_SITES,
+            "The exact solver supports at most {} visitable sites, got {}",
+            Solver::MAX_EXACT_SITES,
+            visitable_sites
+        );
         Ok(World {
+            has_prizes: sites.iter().any(|site| site.prize != 0),
             must_visit: sites
                 .iter()
-                .filter(|site| site.visit == Visit::Always)
+                .filter(|site| site.visit == Visit::Always && !completed_sites.contains(site.id))
                 .map(|site| site.id)
                 .collect(),
+            completed_sites,
+            precedences,
             sites,
-            min_start_at: input.min_start_at,
-            max_end_at: input.max_end_at,
+            travelers,
             ride_matrix,
+            distance_matrix,
+            estimated_rides,
+            break_rules: input.break_rules,
+            solver: input.solver,
+            objectives: Objectives::try_new(input.objectives, input.ranking)?,
             max_tested_extensions: input.max_tested_extensions.try_into()?,
             max_bag_items: input.max_bag_items.try_into()?,
             max_results: input.max_results.try_into()?,
+            max_improvement_steps: input.max_improvement_steps.unwrap_or(0).try_into()?,
+            time_limit: input
+                .time_limit
+                .map(|limit| -> Result<_> {
+                    Ok(std::time::Duration::from_secs(limit.as_s().try_into()?))
+                })
+                .transpose()
+                .context("Invalid time limit")?,
+            threads: input.threads.unwrap_or(0).try_into()?,
         })
     }
 
-    pub fn ride(&self, from: SiteId, to: SiteId) -> Option<Duration> {
-        self.ride_matrix.get(from, to)
+    pub(crate) fn find_site(&self, name: &str) -> Result<&Site> {
+        self.sites
+            .iter()
+            .find(|site| site.name == name)
+            .with_context(|| format!("Could not find site {}", name))
+    }
+
+    pub(crate) fn find_traveler(&self, name: &str) -> Result<&Traveler> {
+        self.travelers
+            .iter()
+            .find(|traveler| traveler.name == name)
+            .with_context(|| format!("Could not find traveler {}", name))
+    }
+
+    pub(crate) fn is_ride_estimated(&self, from: SiteId, to: SiteId) -> bool {
+        self.estimated_rides
+            .get(from.as_usize())
+            .is_some_and(|estimated| estimated.contains(to))
+    }
+
+    pub(crate) fn distance(&self, from: SiteId, to: SiteId) -> Option<Distance> {
+        self.distance_matrix.as_ref()?.get(from, to)
+    }
+
+    /// The precedences that visits to the site must meet
+    pub(crate) fn precedences_to(&self, site: SiteId) -> impl Iterator<Item = &Precedence> {
+        self.precedences
+            .iter()
+            .filter(move |precedence| precedence.after == site)
+    }
+
+    pub(crate) fn ride(&self, from: SiteId, to: SiteId, depart_at: Timestamp) -> Option<Duration> {
+        self.ride_matrix.get(from, to, depart_at)
     }
 
     #[cfg(test)]
-    pub fn mock(mut sites: Vec<Site>) -> Self {
+    pub(crate) fn mock(mut sites: Vec<Site>) -> Self {
         for (i, site) in sites.iter_mut().enumerate() {
             site.id = SiteId::from_usize(i);
             site.name = i.to_string();
@@ -67,13 +275,23 @@ impl World {
         let ride_matrix = RideMatrix::new(sites.len());
         World {
             sites,
-            min_start_at: Timestamp::mock(),
-            max_end_at: None,
+            travelers: vec![Traveler::mock()],
             ride_matrix,
-            must_visit: BTreeSet::new(),
+            distance_matrix: None,
+            estimated_rides: vec![],
+            must_visit: SiteSet::new(),
+            completed_sites: SiteSet::new(),
+            precedences: vec![],
+            break_rules: None,
+            objectives: Objectives::default(),
+            has_prizes: false,
+            solver: Solver::Heuristic,
             max_tested_extensions: 0,
             max_bag_items: 0,
             max_results: 0,
+            max_improvement_steps: 0,
+            time_limit: None,
+            threads: 0,
         }
     }
 }
@@ -85,3 +303,82 @@ impl Index<SiteId> for World {
         &self.sites[index.as_usize()]
     }
 }
+
+impl Index<TravelerId> for World {
+    type Output = Traveler;
+
+    fn index(&self, index: TravelerId) -> &Self::Output {
+        &self.travelers[index.as_usize()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(sites: serde_json::Value) -> input::World {
+        serde_json::from_value(serde_json::json!({
+            "sites": sites,
+            "minStartAt": "09:00",
+            "maxEndAt": null,
+            "maxTestedExtensions": 1,
+            "maxBagItems": 1,
+            "maxResults": 1,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reject_partial_distances() {
+        let sites = serde_json::json!([
+            {"name": "a", "rideDurations": {"b": "10m"}, "rideDistances": {"b": "1km"},
+             "duties": [], "serviceTime": "0s", "canStartHere": true, "visit": "ALWAYS"},
+            {"name": "b", "rideDurations": {"a": "10m"}, "duties": [], "serviceTime": "0s",
+             "canStartHere": true, "visit": "ALWAYS"},
+        ]);
+        let error = World::try_from_json(input(sites.clone())).unwrap_err();
+        assert_eq!(error.to_string(), "Missing ride distance from b to a");
+
+        let mut sites = sites;
+        sites[1]["rideDistances"] = serde_json::json!({"a": "1km"});
+        assert!(World::try_from_json(input(sites)).is_ok());
+    }
+
+    #[test]
+    fn reject_missing_end_sites() {
+        let sites = serde_json::json!([
+            {"name": "a", "rideDurations": {}, "duties": [], "serviceTime": "0s",
+             "canStartHere": true, "visit": "ALWAYS"},
+        ]);
+        let mut input = input(sites);
+        input.path_end = PathEnd::EndSite;
+        let error = World::try_from_json(input.clone()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Traveler default has no end site: no site can end here"
+        );
+
+        input.sites[0].can_end_here = true;
+        assert!(World::try_from_json(input).is_ok());
+    }
+
+    #[test]
+    fn carry_work_from_position() {
+        let sites = serde_json::json!([
+            {"name": "a", "rideDurations": {}, "duties": [], "serviceTime": "0s",
+             "canStartHere": true, "visit": "ALWAYS"},
+        ]);
+        let mut input = input(sites);
+        input.replan = Some(serde_json::from_value(serde_json::json!({
+            "positions": [{"site": "a", "at": "10:30", "totalWork": "1h", "lastBreakEnd": "10:00"}],
+        })).unwrap());
+        let current = World::try_from_json(input.clone()).unwrap().travelers[0]
+            .current
+            .unwrap();
+        assert_eq!(current.total_work, Duration::from_s(3600));
+        assert_eq!(current.work_since_break, Duration::from_s(1800));
+
+        input.replan.as_mut().unwrap().positions[0].last_break_end = Some("11:00".parse().unwrap());
+        assert!(World::try_from_json(input).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 use crate::models::*;
 use nom::bytes::complete::{tag, take};
-use nom::character::complete::u16;
+use nom::character::complete::{u16, u32};
 use nom::combinator::{all_consuming, opt};
+use nom::error::{Error, ErrorKind};
 use nom::sequence::{preceded, terminated};
-use nom::IResult;
+use nom::{Err, IResult};
 
 pub fn parse_duration(input: &str) -> IResult<&str, Duration> {
     let (input, h) = opt(terminated(u16, tag("h")))(input)?;
@@ -17,6 +18,20 @@ pub fn parse_duration(input: &str) -> IResult<&str, Duration> {
     Ok((input, Duration::from_s(h * 3600 + m * 60 + s)))
 }
 
+pub fn parse_distance(input: &str) -> IResult<&str, Distance> {
+    let (input, km) = opt(terminated(u32, tag("km")))(input)?;
+    let (input, m) = opt(terminated(u32, tag("m")))(input)?;
+
+    let m = i32::try_from(km.unwrap_or(0))
+        .ok()
+        .and_then(|km| km.checked_mul(1000))
+        .zip(i32::try_from(m.unwrap_or(0)).ok())
+        .and_then(|(km, m)| km.checked_add(m))
+        .ok_or_else(|| Err::Error(Error::new(input, ErrorKind::TooLarge)))?;
+
+    Ok((input, Distance::from_m(m)))
+}
+
 pub fn parse_2_digits(input: &str) -> IResult<&str, i32> {
     let (input, n_str) = take(2usize)(input)?;
     let (_, n) = all_consuming(u16)(n_str)?;
@@ -49,6 +64,19 @@ mod tests {
         assert_eq!(parse_duration("127s"), Ok(("", Duration::from_s(127))));
     }
 
+    #[test]
+    fn distance() {
+        assert_eq!(
+            parse_distance("12km300m"),
+            Ok(("", Distance::from_m(12_300)))
+        );
+        assert_eq!(parse_distance("850m"), Ok(("", Distance::from_m(850))));
+        assert_eq!(parse_distance("3km"), Ok(("", Distance::from_m(3000))));
+
+        assert!(parse_distance("3000000km").is_err());
+        assert!(parse_distance("2147483km1000m").is_err());
+    }
+
     #[test]
     fn timestamp() {
         assert_eq!(
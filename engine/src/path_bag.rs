@@ -5,92 +5,445 @@ use std::collections::BTreeMap;
 /// Represents a set of non-dominated paths
 ///
 /// This means that each path is not strictly better than any other paths
+///
+/// The items are indexed by a k-d tree over their objective values. A path dominates exactly the
+/// items whose values are all no lesser than its own, so its counters are counted on whole
+/// subtrees, and the counters of the items it dominates are updated with a tag on whole subtrees
+/// too. Each node also knows its worst item. This way, adding or evicting a path only visits the
+/// nodes along the boundaries of two orthants, instead of comparing it with every item.
 #[derive(Debug, Clone)]
 pub struct PathBag {
     items: Vec<PathBagItem>,
+    /// The first node is the root
+    nodes: Vec<Node>,
+    /// The nodes that are not in the tree anymore, to be reused
+    free_nodes: Vec<usize>,
     max_items: usize,
+    objectives: Objectives,
+}
+
+/// Lesser is worse
+type Score = (Reverse<i32>, i32);
+
+/// The number of paths that dominate an item and that it dominates
+type Counters = (i32, i32);
+
+#[derive(Debug, Clone)]
+struct PathBagItem {
+    path: Path,
+    /// The oriented values of the objectives
+    values: Vec<i64>,
+    /// The leaf holding this item
+    leaf: usize,
+    /// The counters, without the tags of the nodes above
+    counters: Counters,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    parent: Option<usize>,
+    /// The bounds of the values of the items below. Removing items does not shrink them
+    min: Vec<i64>,
+    max: Vec<i64>,
+    len: usize,
+    /// Added to the counters of every item below
+    tag: Counters,
+    /// The score and the position of the worst item below, with the tag of this node but not the
+    /// ones of the nodes above. In case of a tie, the one with the least position is the worst
+    worst: Option<(Score, usize)>,
+    kind: NodeKind,
 }
 
 #[derive(Debug, Clone)]
-pub struct PathBagItem {
-    pub path: Path,
-    // How many paths have greater cost
-    pub dominates: i32,
-    // How many paths have smaller cost
-    pub dominated_by: i32,
+enum NodeKind {
+    /// The positions of the items
+    Leaf(Vec<usize>),
+    /// The items whose value for `dimension` is lesser than `value` are in the first child
+    Split {
+        dimension: usize,
+        value: i64,
+        children: [usize; 2],
+    },
 }
 
+const ROOT: usize = 0;
+const MAX_LEAF_ITEMS: usize = 16;
+
 impl PathBag {
-    pub fn new(max_items: usize) -> Self {
+    pub fn new(max_items: usize, objectives: Objectives) -> Self {
         PathBag {
-            items: Vec::with_capacity(max_items + 1),
+            items: vec![],
+            nodes: vec![Node::new(None)],
+            free_nodes: vec![],
             max_items,
+            objectives,
+        }
+    }
+
+    /// A bag that never evicts any path
+    pub fn unbounded(objectives: Objectives) -> Self {
+        PathBag::new(usize::MAX, objectives)
+    }
+
+    /// Keep only the paths that are not dominated by any other
+    pub fn into_pareto_front(self) -> Self {
+        let counters = self.counters();
+        let mut front = PathBag::unbounded(self.objectives);
+        for (item, (dominated_by, _)) in self.items.into_iter().zip(counters) {
+            if dominated_by == 0 {
+                front.add(item.path);
+            }
         }
+        front
     }
 
     pub fn into_paths(self) -> impl Iterator<Item = Path> {
         self.items.into_iter().map(|item| item.path)
     }
 
-    pub fn into_sorted_paths(mut self) -> impl Iterator<Item = PathBagItem> {
-        // Greater scores first
-        self.items.sort_by_key(|item| Reverse(item.score()));
-        self.items.into_iter()
+    /// Sort by the preferred ranking, then by greater scores first. Each path comes with whether
+    /// it is dominated
+    pub fn sorted_paths(&self) -> Vec<(&Path, bool)> {
+        let mut items: Vec<_> = self.items.iter().zip(self.counters()).collect();
+        items.sort_by(|(a, a_counters), (b, b_counters)| {
+            self.objectives
+                .rank(&a.path.cost, &b.path.cost)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| score(*b_counters).cmp(&score(*a_counters)))
+        });
+        items
+            .into_iter()
+            .map(|(item, (dominated_by, _))| (&item.path, dominated_by > 0))
+            .collect()
     }
 
     pub fn add(&mut self, new_path: Path) {
-        let mut new_item = PathBagItem {
+        let will_evict = self.items.len() >= self.max_items;
+        if will_evict && self.items.is_empty() {
+            return;
+        }
+
+        let values = self.objectives.values(&new_path.cost);
+        let (lowest, highest) = (vec![i64::MIN; values.len()], vec![i64::MAX; values.len()]);
+        let equal = self.count_within(ROOT, &values, &values);
+        let dominated_by = self.count_within(ROOT, &lowest, &values) - equal;
+
+        // Every existing path is dominated by at most one more path after the insertion. So, if
+        // the new path is dominated by even more paths, it would be evicted right away
+        let max_dominated_by = self.nodes[ROOT]
+            .worst
+            .map_or(0, |((Reverse(dominated_by), _), _)| dominated_by);
+        if will_evict && dominated_by as i32 > max_dominated_by + 1 {
+            return;
+        }
+        let dominates = self.count_within(ROOT, &values, &highest) - equal;
+
+        // Update dominance counters, then insert the new path
+        self.add_to_orthants(&values, 1);
+        let new_index = self.items.len();
+        self.items.push(PathBagItem {
             path: new_path,
-            dominates: 0,
-            dominated_by: 0,
-        };
+            values,
+            leaf: ROOT,
+            counters: (dominated_by as i32, dominates as i32),
+        });
+        self.insert(new_index);
 
-        let mut worst_index = usize::MAX;
-        let mut worst_score = (Reverse(i32::MIN), i32::MAX); // lesser is worse
+        // Remove worst item
+        if self.items.len() > self.max_items {
+            let (_, worst_index) = self.nodes[ROOT].worst.unwrap();
+            self.remove(worst_index);
+        }
+    }
+
+    /// Remove the item at the given position, updating the dominance counters. The last item takes
+    /// its position
+    fn remove(&mut self, index: usize) {
+        let leaf = self.items[index].leaf;
+        if let NodeKind::Leaf(positions) = &mut self.nodes[leaf].kind {
+            positions.retain(|&i| i != index);
+        }
+        let mut node = Some(leaf);
+        while let Some(current) = node {
+            self.nodes[current].len -= 1;
+            node = self.nodes[current].parent;
+        }
+        self.update_worst_upwards(leaf);
+        let values = std::mem::take(&mut self.items[index].values);
+        self.add_to_orthants(&values, -1);
+        self.items.swap_remove(index);
 
-        // Update dominance counters and find the worst item
-        for (i, item) in self.items.iter_mut().enumerate() {
-            match new_item.path.cost.partial_cmp(&item.path.cost) {
-                Some(Ordering::Less) => {
-                    new_item.dominates += 1;
-                    item.dominated_by += 1;
+        // Point the leaf of the moved item to its new position
+        let last_index = self.items.len();
+        if index != last_index {
+            let leaf = self.items[index].leaf;
+            if let NodeKind::Leaf(positions) = &mut self.nodes[leaf].kind {
+                for position in positions.iter_mut().filter(|i| **i == last_index) {
+                    *position = index;
                 }
-                Some(Ordering::Greater) => {
-                    new_item.dominated_by += 1;
-                    item.dominates += 1;
+            }
+            self.update_worst_upwards(leaf);
+        }
+    }
+
+    /// Add `delta` to the counters of the paths that dominate the given values or are dominated
+    /// by them
+    fn add_to_orthants(&mut self, values: &[i64], delta: i32) {
+        let (lowest, highest) = (vec![i64::MIN; values.len()], vec![i64::MAX; values.len()]);
+        self.add_within(ROOT, values, &highest, (delta, 0));
+        self.add_within(ROOT, &lowest, values, (0, delta));
+        // The paths with the same values are neither
+        self.add_within(ROOT, values, values, (-delta, -delta));
+    }
+
+    /// Count the items below `node` whose values are within the bounds
+    fn count_within(&self, node: usize, min: &[i64], max: &[i64]) -> usize {
+        visit();
+        let current = &self.nodes[node];
+        match current.overlap(min, max) {
+            Overlap::None => 0,
+            Overlap::Full => current.len,
+            Overlap::Partial => match &current.kind {
+                NodeKind::Leaf(positions) => positions
+                    .iter()
+                    .filter(|&&i| {
+                        visit();
+                        is_within(&self.items[i].values, min, max)
+                    })
+                    .count(),
+                NodeKind::Split { children, .. } => children
+                    .iter()
+                    .map(|&child| self.count_within(child, min, max))
+                    .sum(),
+            },
+        }
+    }
+
+    /// Add `delta` to the counters of the items below `node` whose values are within the bounds
+    fn add_within(&mut self, node: usize, min: &[i64], max: &[i64], delta: Counters) {
+        visit();
+        let current = &mut self.nodes[node];
+        match current.overlap(min, max) {
+            Overlap::None => return,
+            Overlap::Full => {
+                current.tag = add(current.tag, delta);
+                current.worst = current
+                    .worst
+                    .map(|(worst_score, i)| (shift(worst_score, delta), i));
+                return;
+            }
+            Overlap::Partial => {}
+        }
+        match &current.kind {
+            NodeKind::Leaf(positions) => {
+                for &i in positions {
+                    visit();
+                    let item = &mut self.items[i];
+                    if is_within(&item.values, min, max) {
+                        item.counters = add(item.counters, delta);
+                    }
+                }
+            }
+            &NodeKind::Split { children, .. } => {
+                for child in children {
+                    self.add_within(child, min, max, delta);
                 }
-                _ => {}
             }
+        }
+        self.update_worst(node);
+    }
 
-            let score = item.score();
-            if score < worst_score {
-                worst_score = score;
-                worst_index = i;
+    /// Put the item at the given position in the tree, rebuilding the highest subtree that got
+    /// unbalanced
+    fn insert(&mut self, index: usize) {
+        let mut path = vec![];
+        let mut node = ROOT;
+        loop {
+            path.push(node);
+            let current = &mut self.nodes[node];
+            let item = &mut self.items[index];
+            current.extend(&item.values);
+            item.counters = add(item.counters, (-current.tag.0, -current.tag.1));
+            match &mut current.kind {
+                NodeKind::Leaf(positions) => {
+                    positions.push(index);
+                    item.leaf = node;
+                    break;
+                }
+                NodeKind::Split {
+                    dimension,
+                    value,
+                    children,
+                } => node = children[usize::from(item.values[*dimension] >= *value)],
             }
         }
 
-        // Insert new path
-        let new_score = new_item.score();
-        if new_score < worst_score {
-            worst_index = self.items.len();
+        let unbalanced = path.windows(2).find_map(|pair| {
+            let (parent, child) = (&self.nodes[pair[0]], &self.nodes[pair[1]]);
+            let is_split = matches!(child.kind, NodeKind::Split { .. });
+            (is_split && parent.len > 2 * MAX_LEAF_ITEMS && 4 * child.len > 3 * parent.len)
+                .then_some(pair[0])
+        });
+        let leaf = &self.nodes[node];
+        let full_leaf = (leaf.len > MAX_LEAF_ITEMS && leaf.min != leaf.max).then_some(node);
+        match unbalanced.or(full_leaf) {
+            Some(subtree) => {
+                let positions = self.take_items(subtree);
+                self.build(subtree, positions);
+                if let Some(parent) = self.nodes[subtree].parent {
+                    self.update_worst_upwards(parent);
+                }
+            }
+            None => self.update_worst_upwards(node),
         }
-        self.items.push(new_item);
+    }
 
-        // Remove worst item and update dominance counters
-        if self.items.len() > self.max_items {
-            let worst_path = self.items.swap_remove(worst_index).path;
-            for item in &mut self.items {
-                match worst_path.cost.partial_cmp(&item.path.cost) {
-                    Some(Ordering::Less) => {
-                        item.dominated_by -= 1;
+    /// Take the items below `node`, folding the tags of the nodes in between into their counters.
+    /// Those nodes are freed
+    fn take_items(&mut self, node: usize) -> Vec<usize> {
+        let mut positions = vec![];
+        let mut pending = vec![(node, (0, 0))];
+        while let Some((current, tag)) = pending.pop() {
+            let tag = match current == node {
+                true => tag,
+                false => {
+                    self.free_nodes.push(current);
+                    add(tag, self.nodes[current].tag)
+                }
+            };
+            match std::mem::replace(&mut self.nodes[current].kind, NodeKind::Leaf(vec![])) {
+                NodeKind::Leaf(items) => {
+                    for i in items {
+                        visit();
+                        self.items[i].counters = add(self.items[i].counters, tag);
+                        positions.push(i);
                     }
-                    Some(Ordering::Greater) => {
-                        item.dominates -= 1;
+                }
+                NodeKind::Split { children, .. } => {
+                    pending.extend(children.map(|child| (child, tag)));
+                }
+            }
+        }
+        positions
+    }
+
+    /// Make `node` the root of a balanced subtree holding the given items, keeping its tag
+    fn build(&mut self, node: usize, positions: Vec<usize>) {
+        let current = &mut self.nodes[node];
+        current.len = 0;
+        for &i in &positions {
+            current.extend(&self.items[i].values);
+        }
+
+        let kind = match self.split_point(&positions) {
+            Some((dimension, value)) if positions.len() > MAX_LEAF_ITEMS => {
+                let (lesser, greater) = positions
+                    .into_iter()
+                    .partition(|&i| self.items[i].values[dimension] < value);
+                let children = [self.new_node(node), self.new_node(node)];
+                self.build(children[0], lesser);
+                self.build(children[1], greater);
+                NodeKind::Split {
+                    dimension,
+                    value,
+                    children,
+                }
+            }
+            _ => {
+                for &i in &positions {
+                    self.items[i].leaf = node;
+                }
+                NodeKind::Leaf(positions)
+            }
+        };
+        self.nodes[node].kind = kind;
+        self.update_worst(node);
+    }
+
+    /// Split on the median of the dimension where the values spread the most. Return `None` if
+    /// all the values are the same
+    fn split_point(&self, positions: &[usize]) -> Option<(usize, i64)> {
+        let dimensions = self.items.get(*positions.first()?)?.values.len();
+        let (dimension, min) = (0..dimensions)
+            .map(|dimension| {
+                let values = positions.iter().map(|&i| self.items[i].values[dimension]);
+                let (min, max) = (values.clone().min().unwrap(), values.max().unwrap());
+                (max - min, dimension, min)
+            })
+            .max()
+            .filter(|&(spread, _, _)| spread > 0)
+            .map(|(_, dimension, min)| (dimension, min))?;
+
+        let mut values: Vec<_> = positions
+            .iter()
+            .map(|&i| self.items[i].values[dimension])
+            .collect();
+        let middle = values.len() / 2;
+        let median = *values.select_nth_unstable(middle).1;
+        // The first child must not be empty
+        let value = match median > min {
+            true => median,
+            false => values.into_iter().filter(|&value| value > min).min()?,
+        };
+        Some((dimension, value))
+    }
+
+    fn new_node(&mut self, parent: usize) -> usize {
+        match self.free_nodes.pop() {
+            Some(node) => {
+                self.nodes[node] = Node::new(Some(parent));
+                node
+            }
+            None => {
+                self.nodes.push(Node::new(Some(parent)));
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Find the worst item below `node` again, from its children or its items
+    fn update_worst(&mut self, node: usize) {
+        let current = &self.nodes[node];
+        let worst = match &current.kind {
+            NodeKind::Leaf(positions) => positions
+                .iter()
+                .map(|&i| (score(self.items[i].counters), i))
+                .min(),
+            NodeKind::Split { children, .. } => children
+                .iter()
+                .filter_map(|&child| self.nodes[child].worst)
+                .min(),
+        };
+        let tag = current.tag;
+        self.nodes[node].worst = worst.map(|(worst_score, i)| (shift(worst_score, tag), i));
+    }
+
+    fn update_worst_upwards(&mut self, node: usize) {
+        let mut node = Some(node);
+        while let Some(current) = node {
+            self.update_worst(current);
+            node = self.nodes[current].parent;
+        }
+    }
+
+    /// The counters of the items, with the tags of all the nodes above
+    fn counters(&self) -> Vec<Counters> {
+        let mut counters = vec![(0, 0); self.items.len()];
+        let mut pending = vec![(ROOT, (0, 0))];
+        while let Some((node, tag)) = pending.pop() {
+            let tag = add(tag, self.nodes[node].tag);
+            match &self.nodes[node].kind {
+                NodeKind::Leaf(positions) => {
+                    for &i in positions {
+                        counters[i] = add(self.items[i].counters, tag);
                     }
-                    _ => {}
+                }
+                NodeKind::Split { children, .. } => {
+                    pending.extend(children.map(|child| (child, tag)));
                 }
             }
         }
+        counters
     }
 
     pub fn len(&self) -> usize {
@@ -104,21 +457,86 @@ impl PathBag {
     /// Return the number of elements by the number of (dominated_by, dominates)
     pub fn count_by_score(&self) -> BTreeMap<(i32, i32), i32> {
         let mut result: BTreeMap<_, i32> = BTreeMap::new();
-        for item in &self.items {
-            *result
-                .entry((item.dominated_by, item.dominates))
-                .or_default() += 1;
+        for counters in self.counters() {
+            *result.entry(counters).or_default() += 1;
         }
         result
     }
 }
 
-impl PathBagItem {
-    fn score(&self) -> (Reverse<i32>, i32) {
-        (Reverse(self.dominated_by), self.dominates)
+enum Overlap {
+    None,
+    Partial,
+    Full,
+}
+
+impl Node {
+    fn new(parent: Option<usize>) -> Self {
+        Node {
+            parent,
+            min: vec![],
+            max: vec![],
+            len: 0,
+            tag: (0, 0),
+            worst: None,
+            kind: NodeKind::Leaf(vec![]),
+        }
+    }
+
+    /// Count one more item below, widening the bounds to its values
+    fn extend(&mut self, values: &[i64]) {
+        if self.len == 0 {
+            self.min = values.to_vec();
+            self.max = values.to_vec();
+        } else {
+            for (i, &value) in values.iter().enumerate() {
+                self.min[i] = self.min[i].min(value);
+                self.max[i] = self.max[i].max(value);
+            }
+        }
+        self.len += 1;
+    }
+
+    fn overlap(&self, min: &[i64], max: &[i64]) -> Overlap {
+        let disjoint =
+            self.len == 0 || (0..min.len()).any(|i| self.max[i] < min[i] || self.min[i] > max[i]);
+        if disjoint {
+            Overlap::None
+        } else if (0..min.len()).all(|i| min[i] <= self.min[i] && self.max[i] <= max[i]) {
+            Overlap::Full
+        } else {
+            Overlap::Partial
+        }
     }
 }
 
+fn is_within(values: &[i64], min: &[i64], max: &[i64]) -> bool {
+    (0..values.len()).all(|i| min[i] <= values[i] && values[i] <= max[i])
+}
+
+fn score((dominated_by, dominates): Counters) -> Score {
+    (Reverse(dominated_by), dominates)
+}
+
+fn shift((Reverse(dominated_by), dominates): Score, delta: Counters) -> Score {
+    (Reverse(dominated_by + delta.0), dominates + delta.1)
+}
+
+fn add(a: Counters, b: Counters) -> Counters {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+#[cfg(test)]
+thread_local! {
+    /// How many nodes and items were looked at, to check that the bag is not scanned
+    static VISITS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+fn visit() {
+    #[cfg(test)]
+    VISITS.with(|visits| visits.set(visits.get() + 1));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,13 +560,13 @@ mod tests {
     /// Insert items that purely dominate each other
     #[test]
     fn pure_dominance() {
-        let mut bag = PathBag::new(3);
+        let mut bag = PathBag::new(3, Objectives::default());
         for c in 0..10 {
             bag.add(path_with_cost(c, c));
         }
         assert_eq!(extract_costs(bag), vec![(0, 0), (1, 1), (2, 2)]);
 
-        let mut bag = PathBag::new(3);
+        let mut bag = PathBag::new(3, Objectives::default());
         for c in (0..10).rev() {
             bag.add(path_with_cost(c, c));
         }
@@ -158,7 +576,7 @@ mod tests {
     /// Insert items without dominance
     #[test]
     fn no_dominance() {
-        let mut bag = PathBag::new(3);
+        let mut bag = PathBag::new(3, Objectives::default());
         for c in 0..10 {
             bag.add(path_with_cost(c, -c));
         }
@@ -221,7 +639,7 @@ mod tests {
             check(&points);
 
             fn check(points: &[(i32, i32, bool)]) {
-                let mut bag = PathBag::new(3);
+                let mut bag = PathBag::new(3, Objectives::default());
                 let mut droppable_by_x = BTreeMap::new();
 
                 for &(x, y, droppable) in points {
@@ -248,4 +666,89 @@ mod tests {
             }
         }
     }
+
+    /// Compare with a straightforward implementation that scans the whole bag
+    #[test]
+    fn same_as_full_scan() {
+        fn naive_add(items: &mut Vec<(i32, i32)>, max_items: usize, new: (i32, i32)) {
+            items.push(new);
+            let counters = |i: usize| {
+                let (a, b) = items[i];
+                let dominates = items
+                    .iter()
+                    .filter(|&&(c, d)| a <= c && b <= d && (a, b) != (c, d))
+                    .count() as i32;
+                let dominated_by = items
+                    .iter()
+                    .filter(|&&(c, d)| c <= a && d <= b && (a, b) != (c, d))
+                    .count() as i32;
+                (Reverse(dominated_by), dominates)
+            };
+            if items.len() > max_items {
+                let worst = (0..items.len()).min_by_key(|&i| counters(i)).unwrap();
+                items.swap_remove(worst);
+            }
+        }
+
+        let mut bag = PathBag::new(20, Objectives::default());
+        let mut naive = vec![];
+        let mut seed = 17i32;
+        for _ in 0..500 {
+            seed = (seed * 1103 + 12345) % 32768;
+            let cost = (seed % 50, (seed / 50) % 50);
+            bag.add(path_with_cost(cost.0, cost.1));
+            naive_add(&mut naive, 20, cost);
+        }
+
+        let mut naive_counts = BTreeMap::new();
+        for &(a, b) in &naive {
+            let dominated_by = naive
+                .iter()
+                .filter(|&&(c, d)| c <= a && d <= b && (a, b) != (c, d))
+                .count() as i32;
+            let dominates = naive
+                .iter()
+                .filter(|&&(c, d)| a <= c && b <= d && (a, b) != (c, d))
+                .count() as i32;
+            *naive_counts.entry((dominated_by, dominates)).or_default() += 1;
+        }
+        assert_eq!(bag.count_by_score(), naive_counts);
+        let costs = bag
+            .into_paths()
+            .map(|path| (path.cost.total_ride.as_s(), path.cost.total_time.as_s()))
+            .collect_vec();
+        assert_eq!(costs, naive);
+    }
+
+    /// Adding a path only looks at the nodes along the boundaries of its orthants, so the work
+    /// grows much slower than the bag
+    #[test]
+    fn sublinear_insertion() {
+        let visits_per_add = |max_items: usize| {
+            let mut bag = PathBag::new(max_items, Objectives::default());
+            let mut seed = 17u64;
+            let mut next = || {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (seed >> 33) as i32 % 100_000
+            };
+            for _ in 0..max_items {
+                bag.add(path_with_cost(next(), next()));
+            }
+            VISITS.with(|visits| visits.set(0));
+            for _ in 0..1000 {
+                bag.add(path_with_cost(next(), next()));
+            }
+            VISITS.with(|visits| visits.get()) / 1000
+        };
+
+        let small = visits_per_add(500);
+        let large = visits_per_add(8000);
+        // Scanning the whole bag would look at 16 times more items
+        assert!(
+            large < 8 * small,
+            "{} visits per path, then {}",
+            small,
+            large
+        );
+    }
 }
@@ -0,0 +1,223 @@
+use crate::cli::ServeOptions;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::json;
+use std::io::{Cursor, Read};
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+use traveling_simon::input::Solver;
+use traveling_simon::{input, output, solve, World};
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+/// Listen for requests, solving each one in one of the workers
+pub fn serve(options: &ServeOptions) -> Result<()> {
+    let server = Server::http(&options.address)
+        .map_err(|error| anyhow!(error))
+        .with_context(|| format!("failed to listen on {}", options.address))?;
+    let server = Arc::new(server);
+    log::info!(
+        "Listening on {} with {} workers",
+        options.address,
+        options.workers
+    );
+
+    let workers: Vec<_> = (0..options.workers.max(1))
+        .map(|_| {
+            let server = server.clone();
+            let options = options.clone();
+            std::thread::spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => handle(&options, request),
+                    Err(error) => log::error!("Failed to receive request: {}", error),
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| anyhow!("a worker stopped unexpectedly"))?;
+    }
+
+    Ok(())
+}
+
+fn handle(options: &ServeOptions, mut request: Request) {
+    let response = match (request.method(), request.url()) {
+        (Method::Get, "/health") => json_response(200, &json!({"status": "ok"})),
+        (Method::Post, "/api/calculate-paths") => calculate_paths(options, &mut request)
+            .unwrap_or_else(|(status, error)| error_response(status, &error)),
+        (_, "/health" | "/api/calculate-paths") => error_response(405, "Method not allowed"),
+        _ => error_response(404, "Not found"),
+    };
+
+    if let Err(error) = request.respond(response) {
+        log::warn!("Failed to respond: {}", error);
+    }
+}
+
+/// Return the answer or the HTTP status and the error message
+fn calculate_paths(
+    options: &ServeOptions,
+    request: &mut Request,
+) -> Result<JsonResponse, (u16, String)> {
+    let body = read_body(options, request.body_length(), request.as_reader())?;
+    let answer = answer(options, &body)?;
+    Ok(json_response(200, &answer))
+}
+
+fn read_body(
+    options: &ServeOptions,
+    length: Option<usize>,
+    reader: impl Read,
+) -> Result<String, (u16, String)> {
+    let too_large = || {
+        let message = format!(
+            "The request must have at most {} bytes",
+            options.max_request_size
+        );
+        (413, message)
+    };
+    if length.is_some_and(|length| length > options.max_request_size) {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    reader
+        .take(options.max_request_size as u64 + 1)
+        .read_to_string(&mut body)
+        .map_err(|error| (400, format!("failed to read request: {}", error)))?;
+    if body.len() > options.max_request_size {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn answer(options: &ServeOptions, body: &str) -> Result<output::Answer, (u16, String)> {
+    let mut world_input: input::World = serde_json::from_str(body)
+        .map_err(|error| (400, format!("failed to parse request: {}", error)))?;
+    limit(options, &mut world_input)?;
+    let world = World::try_from_json(world_input)
+        .map_err(|error| (400, format!("failed to initialize world: {:#}", error)))?;
+
+    catch_panic(|| solve(&world, None))
+}
+
+/// Keep the resources used by a request within the server's limits
+fn limit(options: &ServeOptions, world: &mut input::World) -> Result<(), (u16, String)> {
+    if world.solver == Solver::Exact && !options.allow_exact {
+        return Err((
+            400,
+            "The exact solver is not allowed on this server".to_string(),
+        ));
+    }
+    world.time_limit = Some(match world.time_limit {
+        Some(time_limit) => time_limit.min(options.timeout),
+        None => options.timeout,
+    });
+    world.threads = Some(options.threads);
+    world.max_tested_extensions = world
+        .max_tested_extensions
+        .min(options.max_tested_extensions);
+    world.max_bag_items = world.max_bag_items.min(options.max_bag_items);
+    world.max_results = world.max_results.min(options.max_results);
+    world.max_improvement_steps = world
+        .max_improvement_steps
+        .map(|steps| steps.min(options.max_improvement_steps));
+    Ok(())
+}
+
+/// Turn a panic of the engine into an internal error, so that the worker keeps serving
+fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T, (u16, String)> {
+    std::panic::catch_unwind(AssertUnwindSafe(f))
+        .map_err(|_| (500, "The engine failed unexpectedly".to_string()))?
+        .map_err(|error| (500, format!("{:#}", error)))
+}
+
+fn error_response(status: u16, error: &str) -> JsonResponse {
+    log::warn!("Responding with {}: {}", status, error);
+    json_response(status, &json!({ "error": error }))
+}
+
+fn json_response(status: u16, value: &impl Serialize) -> JsonResponse {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    Response::from_data(body)
+        .with_status_code(status)
+        .with_header(content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn options() -> ServeOptions {
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            options: ServeOptions,
+        }
+        Cli::parse_from(["serve", "--max-request-size", "10"]).options
+    }
+
+    fn greedy_world() -> input::World {
+        serde_json::from_value(json!({
+            "sites": [],
+            "minStartAt": "09:00",
+            "maxEndAt": null,
+            "maxTestedExtensions": 1_000_000,
+            "maxBagItems": 1_000_000,
+            "maxResults": 1_000_000,
+            "maxImprovementSteps": 1_000_000,
+            "threads": 1000,
+            "timeLimit": "1h",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn size_limit() {
+        let options = options();
+        assert_eq!(
+            read_body(&options, None, "0123456789".as_bytes()).unwrap(),
+            "0123456789"
+        );
+        // Without a declared length, the body is still cut at the limit
+        assert_eq!(
+            read_body(&options, None, "0123456789a".as_bytes())
+                .unwrap_err()
+                .0,
+            413
+        );
+        assert_eq!(
+            read_body(&options, Some(11), "".as_bytes()).unwrap_err().0,
+            413
+        );
+    }
+
+    #[test]
+    fn clamp_limits() {
+        let options = options();
+        let mut world = greedy_world();
+        limit(&options, &mut world).unwrap();
+        assert_eq!(world.threads, Some(1));
+        assert_eq!(world.max_tested_extensions, 100);
+        assert_eq!(world.max_bag_items, 10_000);
+        assert_eq!(world.max_results, 100);
+        assert_eq!(world.max_improvement_steps, Some(1000));
+        assert_eq!(world.time_limit, Some(options.timeout));
+
+        let mut world = greedy_world();
+        world.solver = Solver::Exact;
+        assert_eq!(limit(&options, &mut world).unwrap_err().0, 400);
+    }
+
+    #[test]
+    fn panic_is_internal_error() {
+        let error = catch_panic::<()>(|| panic!("boom")).unwrap_err();
+        assert_eq!(error.0, 500);
+        assert_eq!(catch_panic(|| Ok(3)).unwrap(), 3);
+    }
+}
@@ -0,0 +1,415 @@
+use crate::models::*;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Check the world, reporting every issue found instead of stopping at the first one
+pub fn validate(input: &input::World) -> output::Validation {
+    let mut issues = Issues::default();
+    check_names(input, &mut issues);
+    check_duties(input, &mut issues);
+    check_precedences(input, &mut issues);
+
+    // The remaining checks need a consistent world
+    if issues.0.is_empty() {
+        match World::try_from_json(input.clone()) {
+            Ok(world) => {
+                check_start_sites(input, &world, &mut issues);
+                check_reachability(&world, &mut issues);
+            }
+            Err(error) => issues.push("$".to_owned(), format!("{:#}", error)),
+        }
+    }
+
+    output::Validation {
+        valid: issues.0.is_empty(),
+        issues: issues.0,
+    }
+}
+
+#[derive(Debug, Default)]
+struct Issues(Vec<output::Issue>);
+
+impl Issues {
+    fn push(&mut self, path: String, message: String) {
+        self.0.push(output::Issue { path, message });
+    }
+}
+
+fn check_names(input: &input::World, issues: &mut Issues) {
+    let mut site_paths = BTreeMap::new();
+    for (i, site) in input.sites.iter().enumerate() {
+        let path = format!("$.sites[{}].name", i);
+        if let Some(previous) = site_paths.insert(site.name.as_str(), path.clone()) {
+            issues.push(
+                path,
+                format!("Duplicate site name {}, also in {}", site.name, previous),
+            );
+        }
+    }
+    let check_site = |issues: &mut Issues, path: String, name: &str| {
+        if !site_paths.contains_key(name) {
+            issues.push(path, format!("Unknown site {}", name));
+        }
+    };
+
+    for (i, site) in input.sites.iter().enumerate() {
+        for name in site.ride_durations.keys() {
+            check_site(
+                issues,
+                format!("$.sites[{}].rideDurations.{}", i, name),
+                name,
+            );
+        }
+        for name in site.ride_distances.keys() {
+            check_site(
+                issues,
+                format!("$.sites[{}].rideDistances.{}", i, name),
+                name,
+            );
+        }
+        for name in site.ride_profiles.keys() {
+            check_site(
+                issues,
+                format!("$.sites[{}].rideProfiles.{}", i, name),
+                name,
+            );
+        }
+    }
+
+    let mut traveler_paths = BTreeMap::new();
+    for (i, traveler) in input.travelers.iter().enumerate() {
+        for (j, name) in traveler.start_sites.iter().enumerate() {
+            check_site(
+                issues,
+                format!("$.travelers[{}].startSites[{}]", i, j),
+                name,
+            );
+        }
+        for (j, name) in traveler.end_sites.iter().enumerate() {
+            check_site(issues, format!("$.travelers[{}].endSites[{}]", i, j), name);
+        }
+
+        let path = format!("$.travelers[{}].name", i);
+        if let Some(previous) = traveler_paths.insert(traveler.name.as_str(), path.clone()) {
+            issues.push(
+                path,
+                format!(
+                    "Duplicate traveler name {}, also in {}",
+                    traveler.name, previous
+                ),
+            );
+        }
+    }
+
+    for (i, precedence) in input.precedences.iter().enumerate() {
+        check_site(
+            issues,
+            format!("$.precedences[{}].before", i),
+            &precedence.before,
+        );
+        check_site(
+            issues,
+            format!("$.precedences[{}].after", i),
+            &precedence.after,
+        );
+    }
+
+    if let Some(replan) = &input.replan {
+        for (i, position) in replan.positions.iter().enumerate() {
+            check_site(
+                issues,
+                format!("$.replan.positions[{}].site", i),
+                &position.site,
+            );
+            if let Some(started_in) = &position.started_in {
+                check_site(
+                    issues,
+                    format!("$.replan.positions[{}].startedIn", i),
+                    started_in,
+                );
+            }
+            if position
+                .last_break_end
+                .is_some_and(|last_break_end| last_break_end > position.at)
+            {
+                issues.push(
+                    format!("$.replan.positions[{}].lastBreakEnd", i),
+                    "The last break ends after the position".to_string(),
+                );
+            }
+            if let Some(name) = &position.traveler {
+                if !traveler_paths.contains_key(name.as_str()) {
+                    issues.push(
+                        format!("$.replan.positions[{}].traveler", i),
+                        format!("Unknown traveler {}", name),
+                    );
+                }
+            }
+        }
+        for (i, name) in replan.completed_sites.iter().enumerate() {
+            check_site(issues, format!("$.replan.completedSites[{}]", i), name);
+        }
+    }
+}
+
+fn check_duties(input: &input::World, issues: &mut Issues) {
+    // The widest window in which any traveler works
+    let travelers_min_start_at = input
+        .travelers
+        .iter()
+        .map(|traveler| traveler.min_start_at.unwrap_or(input.min_start_at))
+        .min()
+        .unwrap_or(input.min_start_at);
+    let travelers_max_end_at = if input.travelers.is_empty() {
+        input.max_end_at
+    } else {
+        input
+            .travelers
+            .iter()
+            .map(|traveler| traveler.max_end_at.or(input.max_end_at))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|ends| ends.into_iter().max())
+    };
+
+    for (i, site) in input.sites.iter().enumerate() {
+        for (j, duty) in site.duties.iter().enumerate() {
+            let path = format!("$.sites[{}].duties[{}]", i, j);
+            if duty.end() - duty.start() < site.service_time {
+                issues.push(
+                    path.clone(),
+                    format!(
+                        "The duty is shorter than the service time of {}",
+                        site.service_time
+                    ),
+                );
+            }
+            if duty.end() < travelers_min_start_at + site.service_time {
+                issues.push(
+                    path.clone(),
+                    format!(
+                        "The duty ends too soon after minStartAt {}",
+                        travelers_min_start_at
+                    ),
+                );
+            }
+            if let Some(max_end_at) = travelers_max_end_at {
+                if duty.start() + site.service_time > max_end_at {
+                    issues.push(
+                        path,
+                        format!("The duty starts too late before maxEndAt {}", max_end_at),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Report the precedences that are part of a cycle, since no route can meet them
+fn check_precedences(input: &input::World, issues: &mut Issues) {
+    let mut next_sites: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for precedence in &input.precedences {
+        next_sites
+            .entry(precedence.before.as_str())
+            .or_default()
+            .push(precedence.after.as_str());
+    }
+
+    for (i, precedence) in input.precedences.iter().enumerate() {
+        // In a cycle, `before` can be reached back from `after`
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::from([precedence.after.as_str()]);
+        while let Some(site) = queue.pop_front() {
+            if site == precedence.before {
+                issues.push(
+                    format!("$.precedences[{}]", i),
+                    format!(
+                        "The precedence of {} before {} is part of a cycle",
+                        precedence.before, precedence.after
+                    ),
+                );
+                break;
+            }
+            if seen.insert(site) {
+                queue.extend(next_sites.get(site).into_iter().flatten().copied());
+            }
+        }
+    }
+}
+
+fn check_start_sites(input: &input::World, world: &World, issues: &mut Issues) {
+    for traveler in &world.travelers {
+        if traveler.start_sites.is_empty() {
+            let path = if input.travelers.is_empty() {
+                "$.sites".to_owned()
+            } else {
+                format!("$.travelers[{}].startSites", traveler.id.as_usize())
+            };
+            issues.push(
+                path,
+                format!(
+                    "Traveler {} has no start site: no site can start here",
+                    traveler.name
+                ),
+            );
+        }
+    }
+}
+
+fn check_reachability(world: &World, issues: &mut Issues) {
+    let mut reached = SiteSet::new();
+    let mut queue = VecDeque::new();
+    for traveler in &world.travelers {
+        for &site in traveler.departure_sites() {
+            if reached.insert(site) {
+                queue.push_back(site);
+            }
+        }
+    }
+    while let Some(from) = queue.pop_front() {
+        // Sites that are never visited can not be passed through, unless a route starts there
+        let is_departure = world
+            .travelers
+            .iter()
+            .any(|traveler| traveler.departure_sites().contains(&from));
+        if world[from].visit == Visit::Never && !is_departure {
+            continue;
+        }
+        for to in &world.sites {
+            if world.ride_matrix.contains(from, to.id) && reached.insert(to.id) {
+                queue.push_back(to.id);
+            }
+        }
+    }
+
+    for site in world.must_visit.iter() {
+        if !reached.contains(site) {
+            issues.push(
+                format!("$.sites[{}].visit", site.as_usize()),
+                format!(
+                    "Site {} must be visited, but cannot be reached from any start site",
+                    world[site].name
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_every_issue() {
+        let input: input::World = serde_json::from_value(serde_json::json!({
+            "sites": [
+                {"name": "home", "rideDurations": {"a": "10m", "nowhere": "5m"}, "duties": [],
+                 "serviceTime": "0s", "canStartHere": false, "visit": "NEVER"},
+                {"name": "a", "rideDurations": {}, "duties": [{"start": "10:00", "end": "10:10"}],
+                 "serviceTime": "30m", "canStartHere": false, "visit": "ALWAYS"},
+                {"name": "a", "rideDurations": {}, "duties": [{"start": "07:00", "end": "08:00"}],
+                 "serviceTime": "30m", "canStartHere": false, "visit": "ALWAYS"},
+            ],
+            "minStartAt": "09:00",
+            "maxEndAt": null,
+            "maxTestedExtensions": 1,
+            "maxBagItems": 1,
+            "maxResults": 1,
+            "precedences": [
+                {"before": "home", "after": "a"},
+                {"before": "a", "after": "home", "immediately": true},
+            ],
+        }))
+        .unwrap();
+
+        let validation = validate(&input);
+        assert!(!validation.valid);
+        let paths: Vec<_> = validation
+            .issues
+            .iter()
+            .map(|issue| issue.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "$.sites[2].name",
+                "$.sites[0].rideDurations.nowhere",
+                "$.sites[1].duties[0]",
+                "$.sites[2].duties[0]",
+                "$.precedences[0]",
+                "$.precedences[1]",
+            ]
+        );
+
+        // Once consistent, the start sites and the reachability are checked
+        let mut input = input;
+        input.sites[0].ride_durations.remove("nowhere");
+        input.sites[1].duties.clear();
+        input.sites[2].name = "b".to_owned();
+        input.sites[2].duties.clear();
+        input.precedences.pop();
+        let validation = validate(&input);
+        let paths: Vec<_> = validation
+            .issues
+            .iter()
+            .map(|issue| issue.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["$.sites", "$.sites[1].visit", "$.sites[2].visit"]
+        );
+
+        input.sites[0].can_start_here = true;
+        let validation = validate(&input);
+        assert_eq!(validation.issues.len(), 1);
+        assert_eq!(validation.issues[0].path, "$.sites[2].visit");
+    }
+
+    #[test]
+    fn never_pass_through() {
+        let input: input::World = serde_json::from_value(serde_json::json!({
+            "sites": [
+                {"name": "home", "rideDurations": {"closed": "10m"}, "duties": [],
+                 "serviceTime": "0s", "canStartHere": true, "visit": "NEVER"},
+                {"name": "closed", "rideDurations": {"a": "10m"}, "duties": [],
+                 "serviceTime": "0s", "canStartHere": false, "visit": "NEVER"},
+                {"name": "a", "rideDurations": {}, "duties": [{"start": "08:00", "end": "10:00"}],
+                 "serviceTime": "30m", "canStartHere": false, "visit": "ALWAYS"},
+            ],
+            "minStartAt": "07:00",
+            "maxEndAt": null,
+            "maxTestedExtensions": 1,
+            "maxBagItems": 1,
+            "maxResults": 1,
+            "travelers": [
+                {"name": "late", "startSites": ["home"], "minStartAt": "09:45"},
+                {"name": "later", "startSites": ["home"], "minStartAt": "10:00"},
+            ],
+        }))
+        .unwrap();
+
+        let issues = |input: &input::World| {
+            validate(input)
+                .issues
+                .into_iter()
+                .map(|issue| (issue.path, issue.message))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            issues(&input),
+            vec![(
+                "$.sites[2].duties[0]".to_owned(),
+                "The duty ends too soon after minStartAt 09:45".to_owned()
+            )]
+        );
+
+        // The site is only reached through a site that is never visited
+        let mut input = input;
+        input.sites[2].duties.clear();
+        assert_eq!(
+            issues(&input),
+            vec![(
+                "$.sites[2].visit".to_owned(),
+                "Site a must be visited, but cannot be reached from any start site".to_owned()
+            )]
+        );
+    }
+}
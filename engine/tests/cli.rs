@@ -0,0 +1,117 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn world() -> Value {
+    json!({
+        "sites": [
+            {"name": "home", "rideDurations": {"shop": "10m"}, "duties": [],
+             "serviceTime": "0s", "canStartHere": true, "visit": "NEVER"},
+            {"name": "shop", "rideDurations": {}, "duties": [],
+             "serviceTime": "15m", "canStartHere": false, "visit": "ALWAYS"}
+        ],
+        "minStartAt": "09:00",
+        "maxEndAt": null,
+        "maxTestedExtensions": 10,
+        "maxBagItems": 10,
+        "maxResults": 3
+    })
+}
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_traveling-simon"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// An empty directory for the given test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("traveling-simon-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn solve_from_stdin() {
+    for args in [&[][..], &["solve"]] {
+        let output = run(args, &world().to_string());
+        assert!(output.status.success());
+        let answer: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(answer["paths"][0]["routes"][0]["stops"][0]["site"], "shop");
+    }
+}
+
+#[test]
+fn validate_fails_on_issues() {
+    let output = run(&["validate"], &world().to_string());
+    assert!(output.status.success());
+
+    let mut world = world();
+    world["sites"][1]["name"] = json!("home");
+    let output = run(&["validate"], &world.to_string());
+    assert!(!output.status.success());
+    let validation: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(validation["valid"], false);
+}
+
+#[test]
+fn batch_to_output_dir() {
+    let dir = temp_dir("batch");
+    fs::create_dir_all(dir.join("a")).unwrap();
+    fs::create_dir_all(dir.join("b")).unwrap();
+    fs::create_dir_all(dir.join("out")).unwrap();
+    for input in ["a/first.json", "a/second.json", "b/first.json"] {
+        fs::write(dir.join(input), world().to_string()).unwrap();
+    }
+    let path = |name: &str| dir.join(name).display().to_string();
+
+    let output = run(
+        &[
+            "batch",
+            &path("a/first.json"),
+            &path("a/second.json"),
+            "--output-dir",
+            &path("out"),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    for name in ["out/first.json", "out/second.json"] {
+        let answer: Value =
+            serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap();
+        assert_eq!(answer["paths"][0]["routes"][0]["stops"][0]["site"], "shop");
+    }
+
+    // Inputs with the same name would overwrite each other's answer
+    fs::remove_dir_all(dir.join("out")).unwrap();
+    fs::create_dir_all(dir.join("out")).unwrap();
+    let output = run(
+        &[
+            "batch",
+            &path("a/first.json"),
+            &path("b/first.json"),
+            "--output-dir",
+            &path("out"),
+        ],
+        "",
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("would both be written to first.json"));
+    assert_eq!(fs::read_dir(dir.join("out")).unwrap().count(), 0);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
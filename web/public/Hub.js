@@ -8,6 +8,7 @@ class Hub {
     this.rideDurations = new RideDurations()
     this.minStartAt = '09:00'
     this.maxEndAt = null
+    this.pathEnd = 'ANYWHERE'
     this.pathPolyline = null
 
     // eslint-disable-next-line no-template-curly-in-string
@@ -65,6 +66,11 @@ class Hub {
       this.maxEndAt = value === '' ? null : value
       this.persistStorage()
     }
+    $('#path-end').onchange = event => {
+      this.pathEnd = event.currentTarget.value
+      this.persistStorage()
+      this.showSites()
+    }
 
     // Start show-paths pane
     $('.back', this.showPathsPane).onclick = () => {
@@ -93,12 +99,14 @@ class Hub {
         site.serviceTimeMinutes = dataSite.serviceTimeMinutes
         site.duties = dataSite.duties
         site.canStartHere = dataSite.canStartHere
+        site.canEndHere = Boolean(dataSite.canEndHere)
         this.updateSiteMarker(site)
         this.sites.push(site)
       }
       this.rideDurations = RideDurations.fromJSON(data.rideDurations)
       this.minStartAt = data.minStartAt
       this.maxEndAt = data.maxEndAt
+      this.pathEnd = data.pathEnd || 'ANYWHERE'
 
       if (this.sites.length > 0) {
         const bounds = new L.LatLngBounds(this.sites.map(site => site.marker.getLatLng()))
@@ -161,6 +169,9 @@ class Hub {
     }
     this.pushDutyRow('', '')
     $('#site-can-start-here').checked = site.canStartHere
+    // The end sites only matter when the path must end at one of them
+    $('#site-can-end-here').checked = site.canEndHere
+    $('#site-can-end-here').disabled = this.pathEnd !== 'END_SITE'
   }
 
   pushDutyRow (start, end) {
@@ -225,6 +236,7 @@ class Hub {
     this.editingSite.serviceTimeMinutes = Number($('#site-service-time').value)
     this.editingSite.visit = formEl['site-visit'].value
     this.editingSite.canStartHere = $('#site-can-start-here').checked
+    this.editingSite.canEndHere = $('#site-can-end-here').checked
     this.updateSiteMarker(this.editingSite)
 
     this.persistStorage()
@@ -254,6 +266,7 @@ class Hub {
       }
       $('.site-duties', row).textContent = duties
       $('.site-start', row).textContent = site.canStartHere ? 'Yes' : ''
+      $('.site-end', row).textContent = this.pathEnd === 'END_SITE' && site.canEndHere ? 'Yes' : ''
       row.onclick = () => {
         this.editSite(site)
       }
@@ -262,6 +275,7 @@ class Hub {
 
     $('#min-start-at').value = this.minStartAt
     $('#max-end-at').value = this.maxEndAt ? this.maxEndAt : ''
+    $('#path-end').value = this.pathEnd
 
     const empty = this.sites.length === 0
     $('.sites-list', this.showSitesPane).classList.toggle('d-none', empty)
@@ -306,8 +320,14 @@ class Hub {
 
     this.rideDurations.updateForSites(this.sites).then(() =>
       postApi('/api/calculate-paths', this.prepareCalculationWorld())
-    ).then(paths => {
-      this.showPaths(paths)
+    ).then(answer => {
+      if (answer.truncated) {
+        console.warn('The search was stopped by the time limit')
+      }
+      if (answer.diagnosis) {
+        throw new Error(this.describeDiagnosis(answer.diagnosis))
+      }
+      this.showPaths(answer.paths)
     }).catch(error => {
       hide($('.calculating', this.calculatingPathsPane))
       show($('.calculation-error', this.calculatingPathsPane))
@@ -315,6 +335,29 @@ class Hub {
     })
   }
 
+  describeDiagnosis (diagnosis) {
+    const reasons = {
+      NO_RIDE: 'no ride leads there',
+      CLOSED: 'it is closed when it can be reached',
+      TOO_LATE: 'it can not be visited before the end',
+      NOT_EXPLORED: 'it does not fit with the other sites'
+    }
+    const lines = ['No path visits all the sites']
+    for (const site of diagnosis.unreachedSites) {
+      lines.push(`${site.site} was never reached: ${reasons[site.reason]}`)
+    }
+    const window = (site, duty) => duty ? `${site} (shift ${duty.start} - ${duty.end})` : site
+    for (const conflict of diagnosis.windowConflicts) {
+      const first = window(conflict.first, conflict.firstDuty)
+      const second = window(conflict.second, conflict.secondDuty)
+      lines.push(`${first} and ${second} can not be visited one after the other`)
+    }
+    for (const ride of diagnosis.missingRides) {
+      lines.push(`Missing ride from ${ride.from} to ${ride.to}`)
+    }
+    return lines.join('\n')
+  }
+
   showPaths (paths) {
     this.switchPane('showPaths')
     for (const row of $$('.path', this.showPathsPane)) {
@@ -331,8 +374,10 @@ class Hub {
       row.classList.toggle('path-is-dominated', path.isDominated)
       row.classList.toggle('d-none', path.isDominated)
       $('.path-total-ride', row).textContent = path.cost.totalRide
-      const lastStop = path.stops[path.stops.length - 1]
-      $('.path-total-time', row).textContent = `${path.startAt} - ${lastStop.serviceEnd} (${path.cost.totalTime})`
+      const firstRoute = path.routes[0]
+      const lastRoute = path.routes[path.routes.length - 1]
+      const lastStop = lastRoute.stops[lastRoute.stops.length - 1]
+      $('.path-total-time', row).textContent = `${firstRoute.startAt} - ${lastStop.serviceEnd} (${path.cost.totalTime})`
       $('.path-stops', row).textContent = `${path.cost.stops} (${path.cost.stopsOnDuty} in shifts)`
       row.onclick = () => {
         if (activeRow) {
@@ -371,17 +416,25 @@ class Hub {
   }
 
   showPath (path) {
-    const visitedSiteNames = new Set(path.stops.map(stop => stop.site))
+    const visitedSiteNames = new Set()
+    for (const route of path.routes) {
+      for (const stop of route.stops) {
+        visitedSiteNames.add(stop.site)
+      }
+    }
     const siteByName = new Map(this.sites.map(site => [site.name, site]))
 
     for (const site of this.sites) {
       site.marker.setOpacity(visitedSiteNames.has(site.name) ? 1.0 : 0.5)
     }
 
-    const latlngs = [siteByName.get(path.startIn).marker.getLatLng()]
-    for (const stop of path.stops) {
-      latlngs.push(siteByName.get(stop.site).marker.getLatLng())
-    }
+    const latlngs = path.routes.map(route => {
+      const routeLatlngs = [siteByName.get(route.startIn).marker.getLatLng()]
+      for (const stop of route.stops) {
+        routeLatlngs.push(siteByName.get(stop.site).marker.getLatLng())
+      }
+      return routeLatlngs
+    })
 
     if (this.pathPolyline) {
       this.pathPolyline.remove()
@@ -390,20 +443,33 @@ class Hub {
     this.map.fitBounds(this.pathPolyline.getBounds())
 
     show($('.detailed-path', this.showPathsPane))
-    const steps = [
-      [path.startAt, `Start from ${path.startIn}`]
-    ]
-    for (const stop of path.stops) {
-      if (stop.rideStart !== path.startAt) {
-        steps.push([stop.rideStart, `Ride to ${stop.site} in ${stop.ride}`])
-      }
-      if (stop.duty) {
-        steps.push([stop.rideEnd, `Arrive at ${stop.site} for shift ${stop.duty.start} - ${stop.duty.end}`])
-      } else {
-        steps.push([stop.rideEnd, `Arrive at ${stop.site}`])
-      }
-      if (stop.serviceStart !== stop.rideEnd) {
-        steps.push([stop.serviceStart, `Wait ${stop.wait} for shift start`])
+    const steps = []
+    for (const route of path.routes) {
+      const who = path.routes.length === 1 ? '' : `${route.traveler}: `
+      steps.push([route.startAt, `${who}Start from ${route.startIn}`])
+      let prevSite = route.startIn
+      for (const stop of route.stops) {
+        const breakBeforeRide = route.breaks.find(b => b.site === prevSite && b.end === stop.rideStart)
+        if (breakBeforeRide) {
+          steps.push([breakBeforeRide.start, `${who}Take a break until ${breakBeforeRide.end}`])
+        }
+        if (stop.rideStart !== route.startAt) {
+          steps.push([stop.rideStart, `${who}Ride to ${stop.site} in ${stop.ride}`])
+        }
+        if (stop.kind === 'RETURN') {
+          steps.push([stop.rideEnd, `${who}Arrive back at ${stop.site}`])
+        } else if (stop.duty) {
+          steps.push([stop.rideEnd, `${who}Arrive at ${stop.site} for shift ${stop.duty.start} - ${stop.duty.end}`])
+        } else {
+          steps.push([stop.rideEnd, `${who}Arrive at ${stop.site}`])
+        }
+        const breakBeforeService = route.breaks.find(b => b.site === stop.site && b.start === stop.rideEnd)
+        if (breakBeforeService) {
+          steps.push([stop.rideEnd, `${who}Take a break of ${stop.wait}`])
+        } else if (stop.serviceStart !== stop.rideEnd) {
+          steps.push([stop.serviceStart, `${who}Wait ${stop.wait} for ${stop.duty ? 'shift start' : 'opening'}`])
+        }
+        prevSite = stop.site
       }
     }
 
@@ -439,7 +505,10 @@ class Hub {
         duties: site.duties,
         serviceTime: `${site.serviceTimeMinutes}m`,
         visit: site.visit,
-        canStartHere: site.canStartHere
+        canStartHere: site.canStartHere,
+        canEndHere: site.canEndHere,
+        latitude: site.latitude,
+        longitude: site.longitude
       })
     }
 
@@ -447,6 +516,7 @@ class Hub {
       sites,
       minStartAt: this.minStartAt,
       maxEndAt: this.maxEndAt,
+      pathEnd: this.pathEnd,
       maxTestedExtensions: 10,
       maxBagItems: 100,
       maxResults: 10
@@ -481,11 +551,13 @@ class Hub {
         serviceTimeMinutes: site.serviceTimeMinutes,
         visit: site.visit,
         duties: site.duties,
-        canStartHere: site.canStartHere
+        canStartHere: site.canStartHere,
+        canEndHere: site.canEndHere
       })),
       rideDurations: this.rideDurations.toJSON(),
       minStartAt: this.minStartAt,
-      maxEndAt: this.maxEndAt
+      maxEndAt: this.maxEndAt,
+      pathEnd: this.pathEnd
     }
 
     window.localStorage.setItem('hub-data', JSON.stringify(data))
@@ -8,6 +8,7 @@ class Site {
     this.visit = Site.VISIT_ALWAYS
     this.duties = []
     this.canStartHere = false
+    this.canEndHere = false
   }
 }
 
@@ -2,6 +2,9 @@ const express = require('express')
 const router = express.Router()
 const GOOGLE_API_KEY = process.env.GOOGLE_API_KEY
 const ENGINE_PATH = process.env.ENGINE_PATH || '../data/traveling-simon'
+// When set, like 'http://127.0.0.1:3001', the engine started with `traveling-simon serve` is used
+// instead of spawning the binary for each request
+const ENGINE_URL = process.env.ENGINE_URL
 const axios = require('axios')
 const child_process = require('child_process')
 
@@ -52,8 +55,20 @@ router.post('/api/ride-durations', async (req, res, next) => {
   }
 })
 
-// Proxy the call to the engine binary
-router.post('/api/calculate-paths', (req, res, next) => {
+// Proxy the call to the engine server or binary
+router.post('/api/calculate-paths', async (req, res, next) => {
+  if (ENGINE_URL) {
+    try {
+      const answer = await axios.post(`${ENGINE_URL}/api/calculate-paths`, req.body, {
+        validateStatus: () => true
+      })
+      res.status(answer.status)
+      return res.json(answer.data)
+    } catch (error) {
+      return next(error)
+    }
+  }
+
   try {
     const child = child_process.execFile(ENGINE_PATH, (err, stdout, stderr) => {
       if (err) {